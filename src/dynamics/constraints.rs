@@ -0,0 +1,692 @@
+use std::collections::HashMap;
+
+use nalgebra::{DMatrix, DVector, Matrix3, Vector3};
+use rayon::prelude::*;
+use yaml_rust::Yaml;
+
+use crate::dynamics::acceleration_factor;
+use crate::io::input::to_f64;
+use crate::system::topology::read_kind;
+use crate::system::SystemDefinition;
+
+const DEFAULT_TOLERANCE: f64 = 1e-8;
+const DEFAULT_MAX_ITERATIONS: usize = 100;
+
+// Atoms tied together by constraints, which are solved independently of the other clusters
+struct ConstraintCluster {
+    atoms: Vec<usize>,
+    // Constrained pairs as positions in the atoms list, with their length
+    lengths: Vec<(usize, usize, f64)>,
+    // Rigid three-site molecule with the apex atom first, solved analytically
+    settle: bool,
+}
+
+// Holonomic constraints holding the bonds of the given types at a fixed length, and the angles
+// of the given types (in degrees, between two constrained bonds) at a fixed value
+//   constraints:
+//     bonds: {oh: 0.9572}
+//     angles: {hoh: 104.52}
+// SHAKE brings the positions back on the constraints after the drift and RATTLE removes the
+// relative velocities along them after the second kick, both iterating until every constraint
+// is met within the relative tolerance. Symmetric three-site molecules (water) are solved in
+// closed form by SETTLE unless `settle` is false.
+pub struct Constraints {
+    pub tolerance: f64,
+    pub max_iterations: usize,
+    clusters: Vec<ConstraintCluster>,
+}
+
+impl Constraints {
+    pub fn from(definition: &Yaml, system: &SystemDefinition) -> Constraints {
+        let bond_lengths = read_values(&definition["bonds"]);
+        let angle_values = read_values(&definition["angles"]);
+        if bond_lengths.is_empty() {
+            panic!("Constraints require the lengths of the constrained bond types");
+        }
+        let key = |first: usize, second: usize| (first.min(second), first.max(second));
+        let mut lengths = HashMap::new();
+        for bond in &system.topology.bonds {
+            if let Some(length) = bond_lengths.get(&bond.kind) {
+                lengths.insert(key(bond.atoms[0], bond.atoms[1]), *length);
+            }
+        }
+        for angle in &system.topology.angles {
+            if let Some(value) = angle_values.get(&angle.kind) {
+                let [first, apex, last] = angle.atoms;
+                let (first_length, last_length) = match (
+                    lengths.get(&key(first, apex)),
+                    lengths.get(&key(apex, last)),
+                ) {
+                    (Some(x), Some(y)) => (*x, *y),
+                    _ => panic!(
+                        "Constrained angle type {} requires both of its bonds to be constrained",
+                        angle.kind
+                    ),
+                };
+                // Distance between the outer atoms from the law of cosines
+                let length = (first_length.powi(2) + last_length.powi(2)
+                    - 2.0 * first_length * last_length * value.to_radians().cos())
+                .sqrt();
+                lengths.insert(key(first, last), length);
+            }
+        }
+        let mut lengths = lengths.into_iter().collect::<Vec<((usize, usize), f64)>>();
+        lengths.sort_by_key(|(pair, _)| *pair);
+
+        let settle = match &definition["settle"] {
+            Yaml::Boolean(x) => *x,
+            Yaml::BadValue => true,
+            _ => panic!("settle must be a boolean"),
+        };
+        let masses = system
+            .atoms
+            .iter()
+            .map(|atom| atom.mass)
+            .collect::<Vec<f64>>();
+        Constraints {
+            tolerance: match &definition["tolerance"] {
+                Yaml::BadValue => DEFAULT_TOLERANCE,
+                tolerance => to_f64(tolerance),
+            },
+            max_iterations: match &definition["max_iterations"] {
+                Yaml::Integer(x) if *x > 0 => *x as usize,
+                Yaml::BadValue => DEFAULT_MAX_ITERATIONS,
+                _ => panic!("max_iterations must be a positive integer"),
+            },
+            clusters: find_clusters(&lengths, system.atoms.len())
+                .into_iter()
+                .map(|cluster| match settle {
+                    true => as_rigid_molecule(cluster, &masses),
+                    false => cluster,
+                })
+                .collect(),
+        }
+    }
+    // Number of constrained distances, each removing a degree of freedom
+    pub fn count(&self) -> usize {
+        self.clusters.iter().map(|x| x.lengths.len()).sum()
+    }
+    // Brings the starting positions on the constraints, with themselves as reference
+    pub fn initialize(&self, system: &mut SystemDefinition) {
+        let corrections = self.solve_positions(system, true);
+        for (index, displacement, _) in corrections {
+            system.atoms[index].current.position += displacement;
+        }
+    }
+    // Corrects the drifted positions, and the half-step velocities accordingly, returning the
+    // virial of the constraint forces for each atom
+    pub fn constrain_positions(
+        &self,
+        system: &mut SystemDefinition,
+        timestep: f64,
+    ) -> Vec<(usize, Matrix3<f64>)> {
+        let force_factor = 1.0 / (acceleration_factor(&system.units) * timestep.powi(2));
+        self.solve_positions(system, false)
+            .into_iter()
+            .map(|(index, displacement, virial)| {
+                let atom = &mut system.atoms[index];
+                atom.current.position += displacement;
+                atom.current.velocity += displacement / timestep;
+                (index, force_factor * virial)
+            })
+            .collect()
+    }
+    // Removes the relative velocities along the constraints. The virial of the constraint
+    // forces is added to the atoms when a timestep is given.
+    pub fn constrain_velocities(&self, system: &mut SystemDefinition, timestep: Option<f64>) {
+        let corrections = self
+            .clusters
+            .par_iter()
+            .flat_map_iter(|cluster| {
+                let positions = cluster.relative_positions(system, false);
+                let masses = cluster.masses(system);
+                let mut velocities = cluster
+                    .atoms
+                    .iter()
+                    .map(|i| system.atoms[*i].current.velocity)
+                    .collect::<Vec<Vector3<f64>>>();
+                let initial_velocities = velocities.clone();
+                match cluster.settle {
+                    true => cluster.solve_velocities(&positions, &mut velocities, &masses),
+                    false => self.rattle(cluster, &positions, &mut velocities, &masses),
+                }
+                cluster.corrections(&positions, &initial_velocities, &velocities, &masses)
+            })
+            .collect::<Vec<(usize, Vector3<f64>, Matrix3<f64>)>>();
+        let force_factor = timestep.map(|x| 1.0 / (acceleration_factor(&system.units) * x));
+        for (index, velocity_change, virial) in corrections {
+            let atom = &mut system.atoms[index];
+            atom.current.velocity += velocity_change;
+            if let Some(factor) = force_factor {
+                atom.current.virial += factor * virial;
+            }
+        }
+    }
+    // Displacements bringing every cluster on its constraints, with the virial of the mass
+    // weighted displacements for each atom
+    fn solve_positions(
+        &self,
+        system: &SystemDefinition,
+        from_current: bool,
+    ) -> Vec<(usize, Vector3<f64>, Matrix3<f64>)> {
+        self.clusters
+            .par_iter()
+            .flat_map_iter(|cluster| {
+                let reference = cluster.relative_positions(system, !from_current);
+                let initial_positions = cluster.relative_positions(system, false);
+                let masses = cluster.masses(system);
+                let mut positions = initial_positions.clone();
+                match cluster.settle {
+                    true => settle(cluster, &reference, &mut positions, &masses),
+                    false => self.shake(cluster, &reference, &mut positions, &masses),
+                }
+                cluster.corrections(&reference, &initial_positions, &positions, &masses)
+            })
+            .collect()
+    }
+    fn shake(
+        &self,
+        cluster: &ConstraintCluster,
+        reference: &[Vector3<f64>],
+        positions: &mut [Vector3<f64>],
+        masses: &[f64],
+    ) {
+        for _ in 0..self.max_iterations {
+            let mut converged = true;
+            for (i, j, length) in &cluster.lengths {
+                let (i, j) = (*i, *j);
+                let bond = positions[j] - positions[i];
+                let difference = length.powi(2) - bond.norm_squared();
+                if difference.abs() <= 2.0 * self.tolerance * length.powi(2) {
+                    continue;
+                }
+                converged = false;
+                // Displacement along the reference bond, which the constraint force follows
+                let reference_bond = reference[j] - reference[i];
+                let multiplier = difference
+                    / (2.0 * bond.dot(&reference_bond) * (1.0 / masses[i] + 1.0 / masses[j]));
+                positions[i] -= multiplier / masses[i] * reference_bond;
+                positions[j] += multiplier / masses[j] * reference_bond;
+            }
+            if converged {
+                return;
+            }
+        }
+        panic!(
+            "SHAKE did not converge within {} iterations",
+            self.max_iterations
+        );
+    }
+    fn rattle(
+        &self,
+        cluster: &ConstraintCluster,
+        positions: &[Vector3<f64>],
+        velocities: &mut [Vector3<f64>],
+        masses: &[f64],
+    ) {
+        for _ in 0..self.max_iterations {
+            let mut converged = true;
+            for (i, j, length) in &cluster.lengths {
+                let (i, j) = (*i, *j);
+                let bond = positions[j] - positions[i];
+                let relative_velocity = velocities[j] - velocities[i];
+                let projection = bond.dot(&relative_velocity);
+                if projection.abs() <= self.tolerance * length * relative_velocity.norm() {
+                    continue;
+                }
+                converged = false;
+                let multiplier =
+                    -projection / (bond.norm_squared() * (1.0 / masses[i] + 1.0 / masses[j]));
+                velocities[i] -= multiplier / masses[i] * bond;
+                velocities[j] += multiplier / masses[j] * bond;
+            }
+            if converged {
+                return;
+            }
+        }
+        panic!(
+            "RATTLE did not converge within {} iterations",
+            self.max_iterations
+        );
+    }
+}
+
+impl ConstraintCluster {
+    // Positions relative to the first atom of the cluster, as minimum images
+    fn relative_positions(&self, system: &SystemDefinition, previous: bool) -> Vec<Vector3<f64>> {
+        let position = |i: usize| match previous {
+            true => system.atoms[i].previous.position,
+            false => system.atoms[i].current.position,
+        };
+        let origin = position(self.atoms[0]);
+        self.atoms
+            .iter()
+            .map(|i| system.simulation_box.minimum_image(position(*i) - origin))
+            .collect()
+    }
+    fn masses(&self, system: &SystemDefinition) -> Vec<f64> {
+        self.atoms.iter().map(|i| system.atoms[*i].mass).collect()
+    }
+    // Changes of the atoms with the virial sum_k r_k (x) m_k dx_k, shared evenly by the atoms
+    fn corrections(
+        &self,
+        positions: &[Vector3<f64>],
+        initial: &[Vector3<f64>],
+        corrected: &[Vector3<f64>],
+        masses: &[f64],
+    ) -> Vec<(usize, Vector3<f64>, Matrix3<f64>)> {
+        let changes = corrected
+            .iter()
+            .zip(initial)
+            .map(|(x, y)| x - y)
+            .collect::<Vec<Vector3<f64>>>();
+        let virial = positions
+            .iter()
+            .zip(&changes)
+            .zip(masses)
+            .map(|((position, change), mass)| *mass * position * change.transpose())
+            .sum::<Matrix3<f64>>()
+            / self.atoms.len() as f64;
+        self.atoms
+            .iter()
+            .zip(changes)
+            .map(|(index, change)| (*index, change, virial))
+            .collect()
+    }
+    // Exact velocity constraints from the linear system of the multipliers, for small clusters
+    fn solve_velocities(
+        &self,
+        positions: &[Vector3<f64>],
+        velocities: &mut [Vector3<f64>],
+        masses: &[f64],
+    ) {
+        let bonds = self
+            .lengths
+            .iter()
+            .map(|(i, j, _)| (*i, *j, positions[*j] - positions[*i]))
+            .collect::<Vec<(usize, usize, Vector3<f64>)>>();
+        // Effect of the multiplier of constraint l on the velocity of atom k along its bond
+        let weight =
+            |k: usize, (i, j, bond): &(usize, usize, Vector3<f64>)| match (k == *i, k == *j) {
+                (true, _) => -bond / masses[k],
+                (_, true) => bond / masses[k],
+                _ => Vector3::zeros(),
+            };
+        let count = bonds.len();
+        let matrix = DMatrix::from_fn(count, count, |row, column| {
+            let (i, j, bond) = &bonds[row];
+            bond.dot(&(weight(*j, &bonds[column]) - weight(*i, &bonds[column])))
+        });
+        let projections = DVector::from_fn(count, |row, _| {
+            let (i, j, bond) = &bonds[row];
+            -bond.dot(&(velocities[*j] - velocities[*i]))
+        });
+        let multipliers = match matrix.lu().solve(&projections) {
+            Some(x) => x,
+            None => panic!("Singular velocity constraints"),
+        };
+        for (k, velocity) in velocities.iter_mut().enumerate() {
+            for (l, bond) in bonds.iter().enumerate() {
+                *velocity += multipliers[l] * weight(k, bond);
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Constraints {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "SHAKE/RATTLE constraints: {} in {} clusters ({} rigid molecules solved by SETTLE), tolerance {}",
+            self.count(),
+            self.clusters.len(),
+            self.clusters.iter().filter(|x| x.settle).count(),
+            self.tolerance
+        )
+    }
+}
+
+fn read_values(yaml: &Yaml) -> HashMap<String, f64> {
+    match yaml {
+        Yaml::BadValue => HashMap::new(),
+        Yaml::Hash(types) => types
+            .iter()
+            .map(|(kind, value)| (read_kind(kind), to_f64(value)))
+            .collect(),
+        _ => panic!("Constrained values must be given by type"),
+    }
+}
+
+// Groups the constrained pairs connected through shared atoms
+fn find_clusters(lengths: &[((usize, usize), f64)], atoms_count: usize) -> Vec<ConstraintCluster> {
+    fn root(parents: &mut [usize], mut atom: usize) -> usize {
+        while parents[atom] != atom {
+            parents[atom] = parents[parents[atom]];
+            atom = parents[atom];
+        }
+        atom
+    }
+    let mut parents = (0..atoms_count).collect::<Vec<usize>>();
+    for ((first, second), _) in lengths {
+        let (first_root, second_root) = (root(&mut parents, *first), root(&mut parents, *second));
+        parents[first_root] = second_root;
+    }
+    let mut clusters: Vec<ConstraintCluster> = Vec::new();
+    let mut cluster_of_root = HashMap::new();
+    for ((first, second), length) in lengths {
+        let cluster_root = root(&mut parents, *first);
+        let cluster_index = *cluster_of_root.entry(cluster_root).or_insert_with(|| {
+            clusters.push(ConstraintCluster {
+                atoms: Vec::new(),
+                lengths: Vec::new(),
+                settle: false,
+            });
+            clusters.len() - 1
+        });
+        let cluster = &mut clusters[cluster_index];
+        let mut local_index = |atom: usize| match cluster.atoms.iter().position(|x| *x == atom) {
+            Some(x) => x,
+            None => {
+                cluster.atoms.push(atom);
+                cluster.atoms.len() - 1
+            }
+        };
+        let (i, j) = (local_index(*first), local_index(*second));
+        cluster.lengths.push((i, j, *length));
+    }
+    clusters
+}
+
+// Reorders a triangle of constraints around the atom holding two equal lengths to equal masses,
+// leaving any other cluster to SHAKE
+fn as_rigid_molecule(cluster: ConstraintCluster, masses: &[f64]) -> ConstraintCluster {
+    if cluster.atoms.len() != 3 || cluster.lengths.len() != 3 {
+        return cluster;
+    }
+    let length = |a: usize, b: usize| {
+        cluster
+            .lengths
+            .iter()
+            .find(|(i, j, _)| (*i == a && *j == b) || (*i == b && *j == a))
+            .map(|(_, _, x)| *x)
+            .unwrap()
+    };
+    for apex in 0..3 {
+        let (first, second) = ((apex + 1) % 3, (apex + 2) % 3);
+        let (apex_length, base_length) = (length(apex, first), length(first, second));
+        if (apex_length - length(apex, second)).abs() > 1e-10 * apex_length
+            || masses[cluster.atoms[first]] != masses[cluster.atoms[second]]
+        {
+            continue;
+        }
+        return ConstraintCluster {
+            atoms: vec![
+                cluster.atoms[apex],
+                cluster.atoms[first],
+                cluster.atoms[second],
+            ],
+            lengths: vec![
+                (0, 1, apex_length),
+                (0, 2, apex_length),
+                (1, 2, base_length),
+            ],
+            settle: true,
+        };
+    }
+    cluster
+}
+
+// SETTLE of Miyamoto and Kollman (1992): the unconstrained triangle is rotated, about its center
+// of mass, into the rigid geometry in a frame built from the reference plane
+fn settle(
+    cluster: &ConstraintCluster,
+    reference: &[Vector3<f64>],
+    positions: &mut [Vector3<f64>],
+    masses: &[f64],
+) {
+    let (apex_length, base_length) = (cluster.lengths[0].2, cluster.lengths[2].2);
+    let total_mass = masses[0] + 2.0 * masses[1];
+    // Canonical triangle: apex at ra above the center of mass, base at rb below, half width rc
+    let rc = 0.5 * base_length;
+    let height = (apex_length.powi(2) - rc.powi(2)).sqrt();
+    let ra = 2.0 * masses[1] * height / total_mass;
+    let rb = height - ra;
+
+    let center =
+        (masses[0] * positions[0] + masses[1] * (positions[1] + positions[2])) / total_mass;
+    let (a1, b1, c1) = (
+        positions[0] - center,
+        positions[1] - center,
+        positions[2] - center,
+    );
+    let (b0, c0) = (reference[1] - reference[0], reference[2] - reference[0]);
+    let z_axis = b0.cross(&c0).normalize();
+    let x_axis = a1.cross(&z_axis).normalize();
+    let y_axis = z_axis.cross(&x_axis);
+    let (xb0, yb0) = (x_axis.dot(&b0), y_axis.dot(&b0));
+    let (xc0, yc0) = (x_axis.dot(&c0), y_axis.dot(&c0));
+    let za1 = z_axis.dot(&a1);
+    let (xb1, yb1, zb1) = (x_axis.dot(&b1), y_axis.dot(&b1), z_axis.dot(&b1));
+    let (xc1, yc1, zc1) = (x_axis.dot(&c1), y_axis.dot(&c1), z_axis.dot(&c1));
+
+    // Tilt of the plane (phi) and rotation about the apex axis (psi)
+    let sin_phi = za1 / ra;
+    let cos_phi = (1.0 - sin_phi.powi(2)).sqrt();
+    let sin_psi = (zb1 - zc1) / (2.0 * rc * cos_phi);
+    let cos_psi = (1.0 - sin_psi.powi(2)).sqrt();
+    let ya2 = ra * cos_phi;
+    let xb2 = -rc * cos_psi;
+    let yb2 = -rb * cos_phi - rc * sin_psi * sin_phi;
+    let yc2 = -rb * cos_phi + rc * sin_psi * sin_phi;
+
+    // Rotation in the plane (theta) conserving the angular momentum of the displacements
+    let alpha = xb2 * (xb0 - xc0) + yb0 * yb2 + yc0 * yc2;
+    let beta = xb2 * (yc0 - yb0) + xb0 * yb2 + xc0 * yc2;
+    let gamma = xb0 * yb1 - xb1 * yb0 + xc0 * yc1 - xc1 * yc0;
+    let norm = alpha.powi(2) + beta.powi(2);
+    let sin_theta = (alpha * gamma - beta * (norm - gamma.powi(2)).sqrt()) / norm;
+    let cos_theta = (1.0 - sin_theta.powi(2)).sqrt();
+
+    let frame = Matrix3::from_columns(&[x_axis, y_axis, z_axis]);
+    positions[0] = center + frame * Vector3::new(-ya2 * sin_theta, ya2 * cos_theta, za1);
+    positions[1] = center
+        + frame
+            * Vector3::new(
+                xb2 * cos_theta - yb2 * sin_theta,
+                xb2 * sin_theta + yb2 * cos_theta,
+                zb1,
+            );
+    positions[2] = center
+        + frame
+            * Vector3::new(
+                -xb2 * cos_theta - yc2 * sin_theta,
+                -xb2 * sin_theta + yc2 * cos_theta,
+                zc1,
+            );
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use yaml_rust::YamlLoader;
+
+    use super::*;
+    use crate::statics::energetics::{calculate_kinetic_energy, calculate_temperature};
+    use crate::system::tests::molecules;
+    use crate::system::topology::tests::groups;
+    use crate::system::topology::Topology;
+    use crate::thermodynamics::Thermodynamics;
+
+    const OH_LENGTH: f64 = 0.9572;
+    const HOH_ANGLE: f64 = 104.52;
+
+    // Rigid waters spread through a periodic box, the first one straddling its edge
+    fn waters(count: usize) -> SystemDefinition {
+        let (x, y) = (
+            OH_LENGTH * (0.5 * HOH_ANGLE).to_radians().sin(),
+            OH_LENGTH * (0.5 * HOH_ANGLE).to_radians().cos(),
+        );
+        let atoms = (0..count)
+            .flat_map(|molecule| {
+                let origin = Vector3::new(0.2 + 5.0 * molecule as f64, 10.0, 10.0);
+                [
+                    ("O", origin),
+                    ("H", origin + Vector3::new(x, y, 0.0)),
+                    ("H", origin + Vector3::new(-x, y, 0.0)),
+                ]
+            })
+            .collect::<Vec<(&str, Vector3<f64>)>>();
+        let mut topology = Topology::new();
+        for oxygen in (0..count).map(|molecule| 3 * molecule) {
+            topology
+                .bonds
+                .extend(groups("oh", &[[oxygen, oxygen + 1], [oxygen, oxygen + 2]]));
+            topology
+                .angles
+                .extend(groups("hoh", &[[oxygen + 1, oxygen, oxygen + 2]]));
+        }
+        molecules(20.0, [true; 3], &atoms, topology)
+    }
+
+    fn constraints(system: &SystemDefinition, settle: bool) -> Constraints {
+        let definition = YamlLoader::load_from_str(&format!(
+            "{{bonds: {{oh: {}}}, angles: {{hoh: {}}}, settle: {}, tolerance: 1e-12}}",
+            OH_LENGTH, HOH_ANGLE, settle
+        ))
+        .unwrap();
+        Constraints::from(&definition[0], system)
+    }
+
+    // Moves the atoms off the constraints, keeping the rigid geometry as the previous positions
+    fn perturb(system: &mut SystemDefinition) {
+        let mut rng = StdRng::seed_from_u64(17);
+        for atom in system.atoms.iter_mut() {
+            atom.previous.position = atom.current.position;
+            atom.current.position += Vector3::from_fn(|_, _| rng.gen_range(-0.05..0.05));
+            atom.current.velocity = Vector3::zeros();
+        }
+    }
+
+    fn center_of_mass(system: &SystemDefinition, atoms: &[usize]) -> Vector3<f64> {
+        let origin = system.atoms[atoms[0]].current.position;
+        let total_mass = atoms.iter().map(|i| system.atoms[*i].mass).sum::<f64>();
+        origin
+            + atoms
+                .iter()
+                .map(|i| {
+                    let atom = &system.atoms[*i];
+                    atom.mass
+                        * system
+                            .simulation_box
+                            .minimum_image(atom.current.position - origin)
+                })
+                .sum::<Vector3<f64>>()
+                / total_mass
+    }
+
+    fn constrained_pairs(constraints: &Constraints) -> Vec<(usize, usize, f64)> {
+        constraints
+            .clusters
+            .iter()
+            .flat_map(|cluster| {
+                cluster
+                    .lengths
+                    .iter()
+                    .map(|(i, j, length)| (cluster.atoms[*i], cluster.atoms[*j], *length))
+            })
+            .collect()
+    }
+
+    fn separation(system: &SystemDefinition, first: usize, second: usize) -> Vector3<f64> {
+        system.simulation_box.minimum_image(
+            system.atoms[second].current.position - system.atoms[first].current.position,
+        )
+    }
+
+    #[test]
+    fn settle_restores_lengths_and_keeps_center_of_mass() {
+        let mut system = waters(2);
+        let constraints = constraints(&system, true);
+        assert!(constraints.clusters.iter().all(|cluster| cluster.settle));
+        perturb(&mut system);
+        let centers = [[0, 1, 2], [3, 4, 5]].map(|atoms| center_of_mass(&system, &atoms));
+        constraints.constrain_positions(&mut system, 1e-6);
+        for (first, second, length) in constrained_pairs(&constraints) {
+            let distance = separation(&system, first, second).norm();
+            assert!((distance / length - 1.0).abs() < 1e-10);
+        }
+        for (atoms, center) in [[0, 1, 2], [3, 4, 5]].iter().zip(centers) {
+            assert!((center_of_mass(&system, atoms) - center).norm() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn shake_and_settle_agree() {
+        let mut settled = waters(2);
+        let mut shaken = waters(2);
+        perturb(&mut settled);
+        perturb(&mut shaken);
+        constraints(&settled, true).constrain_positions(&mut settled, 1e-6);
+        constraints(&shaken, false).constrain_positions(&mut shaken, 1e-6);
+        for (first, second) in settled.atoms.iter().zip(shaken.atoms.iter()) {
+            let difference = settled
+                .simulation_box
+                .minimum_image(first.current.position - second.current.position);
+            assert!(
+                difference.norm() < 1e-8,
+                "positions differ by {}",
+                difference
+            );
+        }
+    }
+
+    #[test]
+    fn velocities_have_no_component_along_constraints() {
+        for settle in [false, true] {
+            let mut system = waters(2);
+            let constraints = constraints(&system, settle);
+            let mut rng = StdRng::seed_from_u64(23);
+            for atom in system.atoms.iter_mut() {
+                atom.current.velocity = Vector3::from_fn(|_, _| rng.gen_range(-5e3..5e3));
+            }
+            constraints.constrain_velocities(&mut system, None);
+            for (first, second, length) in constrained_pairs(&constraints) {
+                let relative_velocity =
+                    system.atoms[second].current.velocity - system.atoms[first].current.velocity;
+                let projection = separation(&system, first, second).dot(&relative_velocity);
+                assert!(
+                    projection.abs() < 1e-9 * length * relative_velocity.norm(),
+                    "relative velocity {} along the constraint",
+                    projection
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn constraints_reduce_degrees_of_freedom() {
+        let mut system = waters(2);
+        let constraints = constraints(&system, true);
+        assert_eq!(constraints.count(), 6);
+        let definition = YamlLoader::load_from_str(
+            "{ensemble: {type: nve}, velocity: {temperature: 300.0, seed: 3}}",
+        )
+        .unwrap();
+        let mut thermodynamics = Thermodynamics::from(&definition[0]);
+        thermodynamics.constrained_degrees_of_freedom = constraints.count();
+        thermodynamics.initialize(&mut system, Some(&constraints));
+        // Three momentum components and the six constraints are removed from the 18 atomic ones
+        let degrees_of_freedom = thermodynamics.degrees_of_freedom(system.atoms.len());
+        assert_eq!(degrees_of_freedom, 9);
+        let temperature = calculate_temperature(
+            calculate_kinetic_energy(&system.atoms, &system.units),
+            degrees_of_freedom,
+            &system.units,
+        );
+        assert!((temperature / 300.0 - 1.0).abs() < 1e-10);
+        // Momentum drifts away from zero again under a Langevin thermostat
+        thermodynamics.conserves_momentum = false;
+        assert_eq!(thermodynamics.degrees_of_freedom(system.atoms.len()), 12);
+    }
+}
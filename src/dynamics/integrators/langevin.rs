@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use nalgebra::Vector3;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rand_distr::{Distribution, StandardNormal};
+use rayon::prelude::*;
+use yaml_rust::Yaml;
+
+use crate::dynamics::neighbors::NeighborsList;
+use crate::dynamics::NextStepCalculation;
+use crate::dynamics::{acceleration_factor, evaluate_forces};
+use crate::io::input::to_f64;
+use crate::statics::models::PotentialModel;
+use crate::system::atom::Atom;
+use crate::system::SystemDefinition;
+
+pub enum LangevinFriction {
+    Uniform(f64),
+    PerSpecies(HashMap<String, f64>), // Species missing from the table are not thermostatted
+}
+
+impl LangevinFriction {
+    fn from(yaml: &Yaml) -> LangevinFriction {
+        let friction = match yaml {
+            Yaml::Hash(species) => LangevinFriction::PerSpecies(
+                species
+                    .iter()
+                    .map(|(name, value)| (name.as_str().unwrap().to_string(), to_f64(value)))
+                    .collect::<HashMap<String, f64>>(),
+            ),
+            _ => LangevinFriction::Uniform(to_f64(yaml)),
+        };
+        let any_negative = match &friction {
+            LangevinFriction::Uniform(x) => *x < 0.0,
+            LangevinFriction::PerSpecies(x) => x.values().any(|value| *value < 0.0),
+        };
+        if any_negative {
+            panic!("Friction coefficient must not be negative");
+        }
+        friction
+    }
+    fn for_atom(&self, atom: &Atom) -> f64 {
+        match self {
+            LangevinFriction::Uniform(x) => *x,
+            LangevinFriction::PerSpecies(x) => *x.get(&atom.name).unwrap_or(&0.0),
+        }
+    }
+}
+
+// Langevin dynamics with the BAOAB splitting (Leimkuhler and Matthews, 2013)
+pub struct LangevinIntegrator {
+    pub timestep: f64,
+    pub temperature: f64,
+    pub friction: LangevinFriction,
+    pub seed: u64,
+    generator: StdRng,
+}
+
+impl LangevinIntegrator {
+    pub fn from(yaml: &Yaml) -> LangevinIntegrator {
+        let timestep = yaml["timestep"].as_f64().unwrap();
+        let integrator_definition = &yaml["integrator"];
+        if !integrator_definition["constraints"].is_badvalue() {
+            panic!("Constraints are only supported by the Verlet integrator");
+        }
+        let seed = match &integrator_definition["seed"] {
+            Yaml::Integer(x) => *x as u64,
+            Yaml::BadValue => rand::random::<u64>(),
+            _ => panic!("Seed must be an integer"),
+        };
+        let temperature = to_f64(&integrator_definition["temperature"]);
+        if temperature < 0.0 {
+            panic!("Langevin temperature must not be negative");
+        }
+        LangevinIntegrator {
+            timestep,
+            temperature,
+            friction: LangevinFriction::from(&integrator_definition["friction"]),
+            seed,
+            generator: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl std::fmt::Display for LangevinIntegrator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Langevin BAOAB integrator at {} (seed: {})",
+            self.temperature, self.seed
+        )
+    }
+}
+
+impl NextStepCalculation for LangevinIntegrator {
+    fn next_step(
+        &mut self,
+        system: &mut SystemDefinition,
+        potential: &PotentialModel,
+        neighbors: &mut NeighborsList,
+    ) {
+        let acceleration_factor = acceleration_factor(&system.units);
+        let thermal_energy = system.units.boltzmann_constant() * self.temperature;
+        let kinetic_energy_factor = system.units.kinetic_energy_factor();
+        // Noise is drawn sequentially so that a given seed reproduces the same trajectory
+        let noise = (0..system.atoms.len())
+            .map(|_| {
+                Vector3::new(
+                    StandardNormal.sample(&mut self.generator),
+                    StandardNormal.sample(&mut self.generator),
+                    StandardNormal.sample(&mut self.generator),
+                )
+            })
+            .collect::<Vec<Vector3<f64>>>();
+        system
+            .atoms
+            .par_iter_mut()
+            .zip(noise.par_iter())
+            .for_each(|(atom, atom_noise)| {
+                atom.previous = atom.current.cache();
+                // B
+                atom.current.velocity +=
+                    0.5 * self.timestep * acceleration_factor * atom.previous.force / atom.mass;
+                // A
+                atom.current.position += 0.5 * self.timestep * atom.current.velocity;
+                // O
+                let damping = (-self.friction.for_atom(atom) * self.timestep).exp();
+                let thermal_velocity =
+                    (thermal_energy / (atom.mass * kinetic_energy_factor)).sqrt();
+                atom.current.velocity = damping * atom.current.velocity
+                    + (1.0 - damping.powi(2)).sqrt() * thermal_velocity * atom_noise;
+                // A
+                atom.current.position += 0.5 * self.timestep * atom.current.velocity;
+            });
+        evaluate_forces(system, potential, neighbors);
+        // B
+        system.atoms.par_iter_mut().for_each(|atom| {
+            atom.current.velocity +=
+                0.5 * self.timestep * acceleration_factor * atom.current.force / atom.mass;
+        });
+    }
+}
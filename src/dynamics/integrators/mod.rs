@@ -1 +1,2 @@
+pub mod langevin;
 pub mod verlet;
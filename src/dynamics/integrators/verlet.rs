@@ -1,45 +1,73 @@
 use rayon::prelude::*;
 
+use crate::dynamics::constraints::Constraints;
 use crate::dynamics::neighbors::NeighborsList;
 use crate::dynamics::NextStepCalculation;
+use crate::dynamics::{acceleration_factor, evaluate_forces};
 use crate::statics::models::PotentialModel;
-use crate::system::atom::Atom;
+use crate::system::SystemDefinition;
 
 pub struct VerletIntegrator {
     pub timestep: f64,
+    pub constraints: Option<Constraints>,
 }
 
 impl VerletIntegrator {
-    pub fn from(yaml: &yaml_rust::Yaml) -> VerletIntegrator {
+    pub fn from(yaml: &yaml_rust::Yaml, system: &SystemDefinition) -> VerletIntegrator {
         let timestep = yaml["timestep"].as_f64().unwrap();
-        VerletIntegrator::new(timestep)
+        let mut integrator = VerletIntegrator::new(timestep);
+        integrator.constraints = match &yaml["integrator"]["constraints"] {
+            yaml_rust::Yaml::BadValue => None,
+            definition => Some(Constraints::from(definition, system)),
+        };
+        integrator
     }
     pub fn new(timestep: f64) -> VerletIntegrator {
-        VerletIntegrator { timestep }
+        VerletIntegrator {
+            timestep,
+            constraints: None,
+        }
     }
 }
 
 impl std::fmt::Display for VerletIntegrator {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "Velocity Verlet integrator")
+        match &self.constraints {
+            Some(constraints) => write!(f, "Velocity Verlet integrator with {}", constraints),
+            None => write!(f, "Velocity Verlet integrator"),
+        }
     }
 }
 
 impl NextStepCalculation for VerletIntegrator {
     fn next_step(
         &mut self,
-        atoms: &mut Vec<Atom>,
+        system: &mut SystemDefinition,
         potential: &PotentialModel,
         neighbors: &mut NeighborsList,
     ) -> () {
-        atoms.par_iter_mut().for_each(|atom| {
+        let acceleration_factor = acceleration_factor(&system.units);
+        system.atoms.par_iter_mut().for_each(|atom| {
             atom.previous = atom.current.cache();
-            let half_step_velocity =
-                atom.previous.velocity + 0.5 * self.timestep * atom.previous.force / atom.mass;
-            atom.current.position = atom.previous.position + self.timestep * half_step_velocity;
-            potential.update(atom, neighbors);
-            atom.current.velocity =
-                half_step_velocity + 0.5 * self.timestep * atom.current.force / atom.mass;
+            atom.current.velocity +=
+                0.5 * self.timestep * acceleration_factor * atom.previous.force / atom.mass;
+            atom.current.position += self.timestep * atom.current.velocity;
         });
+        let constraint_virials = match &self.constraints {
+            Some(constraints) => constraints.constrain_positions(system, self.timestep),
+            None => Vec::new(),
+        };
+        evaluate_forces(system, potential, neighbors);
+        // The position constraints were resolved before the virials were reset
+        for (index, virial) in constraint_virials {
+            system.atoms[index].current.virial += virial;
+        }
+        system.atoms.par_iter_mut().for_each(|atom| {
+            atom.current.velocity +=
+                0.5 * self.timestep * acceleration_factor * atom.current.force / atom.mass;
+        });
+        if let Some(constraints) = &self.constraints {
+            constraints.constrain_velocities(system, Some(self.timestep));
+        }
     }
 }
@@ -1,50 +1,42 @@
+pub mod constraints;
 pub mod integrators;
 pub mod neighbors;
 
-use rayon::prelude::*;
-
+use crate::dynamics::constraints::Constraints;
 use crate::dynamics::neighbors::NeighborsList;
 use crate::statics::models::PotentialModel;
-use crate::system::atom::Atom;
+use crate::system::SystemDefinition;
 use crate::utils::metrics::UnitSystem;
 
 pub enum DynamicsIntegrator {
     Verlet(integrators::verlet::VerletIntegrator),
+    Langevin(Box<integrators::langevin::LangevinIntegrator>),
 }
 
 impl DynamicsIntegrator {
-    fn convert(&self, atom: &mut Atom, unit_system: &UnitSystem) -> () {
-        atom.current.position *= unit_system.distance.0;
-        atom.current.velocity *= unit_system.distance.0 / unit_system.time.0;
-        atom.current.force *= unit_system.force.0;
-        atom.current.potential_energy *= unit_system.energy.0;
-    }
-    fn revert(&self, atom: &mut Atom, unit_system: &UnitSystem) -> () {
-        atom.current.position /= unit_system.distance.0;
-        atom.current.velocity /= unit_system.distance.0 / unit_system.time.0;
-        atom.current.force /= unit_system.force.0;
-        atom.current.potential_energy /= unit_system.energy.0;
-    }
     pub fn next_step(
         &mut self,
-        atoms: &mut Vec<Atom>,
+        system: &mut SystemDefinition,
         potential: &PotentialModel,
         neighbors: &mut NeighborsList,
-        unit_system: &UnitSystem,
     ) -> () {
-        atoms.
-            par_iter_mut().
-            for_each(|atom| {
-                self.convert(atom, unit_system);
-            });
         match self {
-            DynamicsIntegrator::Verlet(x) => x.next_step(atoms, potential, neighbors),
+            DynamicsIntegrator::Verlet(x) => x.next_step(system, potential, neighbors),
+            DynamicsIntegrator::Langevin(x) => x.next_step(system, potential, neighbors),
         };
-        atoms.
-            par_iter_mut().
-            for_each(|atom| {
-                self.revert(atom, unit_system);
-            });
+    }
+    pub fn constraints(&self) -> Option<&Constraints> {
+        match self {
+            DynamicsIntegrator::Verlet(x) => x.constraints.as_ref(),
+            DynamicsIntegrator::Langevin(_) => None,
+        }
+    }
+    // Random and friction forces of the Langevin thermostat do not cancel between atoms
+    pub fn conserves_momentum(&self) -> bool {
+        match self {
+            DynamicsIntegrator::Verlet(_) => true,
+            DynamicsIntegrator::Langevin(_) => false,
+        }
     }
 }
 
@@ -53,6 +45,7 @@ impl std::fmt::Display for DynamicsIntegrator {
         let indent = 2;
         let integrator_description = match self {
             DynamicsIntegrator::Verlet(x) => format!("{:indent$}{}", "", x, indent = indent),
+            DynamicsIntegrator::Langevin(x) => format!("{:indent$}{}", "", x, indent = indent),
         };
         write!(f, "Integrator:\n{}", integrator_description)
     }
@@ -62,10 +55,26 @@ impl std::fmt::Display for DynamicsIntegrator {
 trait NextStepCalculation {
     fn next_step(
         &mut self,
-        atoms: &mut Vec<Atom>,
+        system: &mut SystemDefinition,
         potential: &PotentialModel,
         neighbors: &mut NeighborsList,
     ) -> () {
         panic!("Not implemented");
     }
 }
+
+// Forces are expressed in energy per distance, so they have to be rescaled
+// to give accelerations in distance per squared time of the active unit system
+pub fn acceleration_factor(unit_system: &UnitSystem) -> f64 {
+    1.0 / unit_system.kinetic_energy_factor()
+}
+
+// Brings the neighbors list up to date with the current positions and recomputes the forces
+pub fn evaluate_forces(
+    system: &mut SystemDefinition,
+    potential: &PotentialModel,
+    neighbors: &mut NeighborsList,
+) {
+    neighbors.update(system);
+    potential.evaluate(system, neighbors);
+}
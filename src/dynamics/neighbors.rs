@@ -1,35 +1,63 @@
-use std::{collections::HashMap, ops::Deref};
+use std::collections::HashMap;
 
 use nalgebra::Vector3;
 
+use crate::io::input::to_f64;
 use crate::system::SystemDefinition;
 
 use rayon::prelude::*;
 
-
 #[derive(Debug, Clone)]
 pub struct NeighborsListEntry {
     pub index: u64,
     pub distance_vector: Vector3<f64>,
     pub distance: f64,
+    pub separation: u8, // Bonds between both atoms for 1-2, 1-3 and 1-4 pairs, 0 otherwise
+}
+
+pub enum NeighborsSearch {
+    BruteForce,  // Every pair of atoms is checked, O(N^2)
+    LinkedCells, // Atoms are binned into cells at least as wide as the cutoff, O(N)
 }
 
 pub struct NeighborsList {
     pub neighbors: HashMap<u64, Vec<NeighborsListEntry>>,
     pub log: bool,
+    pub search: NeighborsSearch,
+    pub half: bool,    // Each pair is stored once, under its lowest index
+    pub rebuilds: u64, // Number of times the list was built from scratch
+    pub updates: u64,  // Number of times the list was brought up to date
     cutoff: f64,
+    skin: f64,
+    reference_positions: Vec<Vector3<f64>>, // Positions at the time of the last rebuild
 }
 
 impl std::fmt::Display for NeighborsListEntry {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(
             f,
-            "(index: {}, distance_vector: {:?}, distance: {})",
-            self.index, self.distance_vector, self.distance
+            "(index: {}, distance_vector: {:?}, distance: {}, separation: {})",
+            self.index, self.distance_vector, self.distance, self.separation
         )
     }
 }
 
+fn construct_entry(
+    system: &SystemDefinition,
+    index: usize,
+    neighbor_index: usize,
+) -> NeighborsListEntry {
+    let distance_vector = system.simulation_box.minimum_image(
+        system.atoms[neighbor_index].current.position - system.atoms[index].current.position,
+    );
+    NeighborsListEntry {
+        index: neighbor_index as u64,
+        distance_vector,
+        distance: distance_vector.norm(),
+        separation: system.topology.separation(index, neighbor_index),
+    }
+}
+
 impl NeighborsList {
     pub fn from(neighbors_settings: &yaml_rust::Yaml) -> NeighborsList {
         NeighborsList {
@@ -45,6 +73,40 @@ impl NeighborsList {
                 yaml_rust::Yaml::Boolean(log) => *log,
                 _ => panic!("Log must be a boolean"),
             },
+            search: match neighbors_settings["method"].as_str() {
+                Some("linked_cells") | None => NeighborsSearch::LinkedCells,
+                Some("brute_force") => NeighborsSearch::BruteForce,
+                _ => panic!("Unknown neighbors search method"),
+            },
+            half: match &neighbors_settings["half"] {
+                yaml_rust::Yaml::Boolean(x) => *x,
+                yaml_rust::Yaml::BadValue => false,
+                _ => panic!("half must be a boolean"),
+            },
+            rebuilds: 0,
+            updates: 0,
+            skin: match &neighbors_settings["skin"] {
+                yaml_rust::Yaml::BadValue => 0.0,
+                skin => match to_f64(skin) >= 0.0 {
+                    true => to_f64(skin),
+                    false => panic!("Skin must not be negative"),
+                },
+            },
+            reference_positions: Vec::new(),
+        }
+    }
+    // Pairs are stored up to the cutoff extended by the skin, so that the list stays valid
+    // until some atom travels more than half of the skin
+    pub fn cutoff(&self) -> f64 {
+        self.cutoff
+    }
+    fn list_cutoff(&self) -> f64 {
+        self.cutoff + self.skin
+    }
+    fn is_listed(&self, index: usize, neighbor_index: usize) -> bool {
+        match self.half {
+            true => neighbor_index > index,
+            false => neighbor_index != index,
         }
     }
     fn update_for_atom(&mut self, index: usize, system: &SystemDefinition) -> () {
@@ -52,59 +114,174 @@ impl NeighborsList {
             .atoms
             .par_iter()
             .enumerate()
-            .filter(|(j, _)| *j != index)
-            .map(|(neighbor_index, neighbor)| {
-                let mut distance_vector = Vector3::<f64>::new(
-                    neighbor.current.position[0]
-                        - system.atoms[index as usize].current.position[0],
-                    neighbor.current.position[1]
-                        - system.atoms[index as usize].current.position[1],
-                    neighbor.current.position[2]
-                        - system.atoms[index as usize].current.position[2],
-                );
-                system
-                    .simulation_box
-                    .vectors
-                    .row_iter()
-                    .for_each(|basis_vector| {
-                        let basis_vector =
-                            Vector3::<f64>::new(basis_vector[0], basis_vector[1], basis_vector[2]);
-                        let distance_vector_projection =
-                            distance_vector.dot(&basis_vector) / basis_vector.norm();
-                        let relative_coordinate = distance_vector_projection / basis_vector.norm();
-                        let minimum_image_coefficient =
-                            (relative_coordinate <= -0.5) as i64 as f64 + (relative_coordinate > 0.5) as i64 as f64 * -1.;
-                        if minimum_image_coefficient != 0.0 {
-                            distance_vector += minimum_image_coefficient * basis_vector;
-                        };
-                    });
-                NeighborsListEntry {
-                    index: neighbor_index as u64,
-                    distance_vector: distance_vector,
-                    distance: distance_vector.norm(),
-                }
-            })
-            .filter(|entry| entry.distance < self.cutoff)
+            .filter(|(j, _)| self.is_listed(index, *j))
+            .map(|(neighbor_index, _)| construct_entry(system, index, neighbor_index))
+            .filter(|entry| entry.distance < self.list_cutoff())
             .collect::<Vec<NeighborsListEntry>>();
         self.neighbors
             .insert(index.try_into().unwrap(), new_neighbors);
     }
-    pub fn update(&mut self, system: &mut SystemDefinition) -> () {
-        system.wrap_atom_positions();
-        self.neighbors.clear();
-        system
+    fn update_with_linked_cells(&mut self, system: &SystemDefinition) {
+        let simulation_box = &system.simulation_box;
+        let widths = simulation_box.perpendicular_widths();
+        let cells_count: [usize; 3] =
+            [0, 1, 2].map(|i| ((widths[i] / self.list_cutoff()).floor() as usize).max(1));
+
+        // Bin indices come from fractional coordinates, wrapped only along periodic directions.
+        // Atoms outside of a non-periodic edge land in the outermost bin, which is still safe
+        // since the bins are at least one cutoff wide.
+        let atom_cells = system
             .atoms
+            .par_iter()
+            .map(|atom| {
+                let fractional_position = simulation_box.to_fractional(&atom.current.position);
+                [0, 1, 2].map(|i| {
+                    let coordinate = match simulation_box.periodicity[i] {
+                        true => fractional_position[i] - fractional_position[i].floor(),
+                        false => fractional_position[i],
+                    };
+                    ((coordinate * cells_count[i] as f64).floor().max(0.0) as usize)
+                        .min(cells_count[i] - 1)
+                })
+            })
+            .collect::<Vec<[usize; 3]>>();
+        let flatten =
+            |cell: [usize; 3]| (cell[0] * cells_count[1] + cell[1]) * cells_count[2] + cell[2];
+        let mut cells: Vec<Vec<usize>> =
+            vec![Vec::new(); cells_count[0] * cells_count[1] * cells_count[2]];
+        atom_cells
             .iter()
             .enumerate()
-            .for_each(|(index, _)| self.update_for_atom(index, system));
+            .for_each(|(index, cell)| cells[flatten(*cell)].push(index));
+
+        let new_neighbors = atom_cells
+            .par_iter()
+            .enumerate()
+            .map(|(index, cell)| {
+                // Adjacent cells, with duplicates removed when a periodic direction has
+                // fewer than three cells and the stencil wraps onto itself
+                let mut stencil: Vec<usize> = Vec::with_capacity(27);
+                for offset in (0..27i64).map(|k| [k / 9 - 1, k / 3 % 3 - 1, k % 3 - 1]) {
+                    let mut adjacent_cell = [0usize; 3];
+                    let mut valid = true;
+                    for i in 0..3 {
+                        let shifted = cell[i] as i64 + offset[i];
+                        let count = cells_count[i] as i64;
+                        adjacent_cell[i] = match simulation_box.periodicity[i] {
+                            true => shifted.rem_euclid(count) as usize,
+                            false if shifted >= 0 && shifted < count => shifted as usize,
+                            false => {
+                                valid = false;
+                                0
+                            }
+                        };
+                    }
+                    if valid {
+                        stencil.push(flatten(adjacent_cell));
+                    }
+                }
+                stencil.sort_unstable();
+                stencil.dedup();
+                let mut entries = stencil
+                    .iter()
+                    .flat_map(|adjacent_cell| cells[*adjacent_cell].iter())
+                    .filter(|neighbor_index| self.is_listed(index, **neighbor_index))
+                    .map(|neighbor_index| construct_entry(system, index, *neighbor_index))
+                    .filter(|entry| entry.distance < self.list_cutoff())
+                    .collect::<Vec<NeighborsListEntry>>();
+                entries.sort_by_key(|entry| entry.index);
+                (index as u64, entries)
+            })
+            .collect::<Vec<(u64, Vec<NeighborsListEntry>)>>();
+        self.neighbors = new_neighbors.into_iter().collect();
+    }
+    fn needs_rebuild(&self, system: &SystemDefinition) -> bool {
+        if self.reference_positions.len() != system.atoms.len() {
+            return true;
+        }
+        let max_displacement = system
+            .atoms
+            .par_iter()
+            .zip(self.reference_positions.par_iter())
+            .map(|(atom, reference_position)| {
+                system
+                    .simulation_box
+                    .minimum_image(atom.current.position - reference_position)
+                    .norm()
+            })
+            .reduce(|| 0.0, f64::max);
+        max_displacement > 0.5 * self.skin
+    }
+    // Minimum images are only unique when the sphere of interaction fits in the box
+    fn check_cutoff(&self, system: &SystemDefinition) {
+        let widths = system.simulation_box.perpendicular_widths();
+        for i in 0..3 {
+            if system.simulation_box.periodicity[i] && self.list_cutoff() > 0.5 * widths[i] {
+                panic!(
+                    "Neighbors cutoff with skin ({}) exceeds half of the box width ({}) along periodic direction {}",
+                    self.list_cutoff(),
+                    widths[i],
+                    i
+                );
+            }
+        }
     }
+    fn rebuild(&mut self, system: &SystemDefinition) {
+        self.check_cutoff(system);
+        self.neighbors.clear();
+        match self.search {
+            NeighborsSearch::BruteForce => system
+                .atoms
+                .iter()
+                .enumerate()
+                .for_each(|(index, _)| self.update_for_atom(index, system)),
+            NeighborsSearch::LinkedCells => self.update_with_linked_cells(system),
+        }
+        self.reference_positions = system
+            .atoms
+            .iter()
+            .map(|atom| atom.current.position)
+            .collect();
+        self.rebuilds += 1;
+    }
+    // Recomputes distances of the stored pairs without searching for new ones
+    fn refresh(&mut self, system: &SystemDefinition) {
+        self.neighbors.par_iter_mut().for_each(|(index, entries)| {
+            entries.iter_mut().for_each(|entry| {
+                *entry = construct_entry(system, *index as usize, entry.index as usize)
+            })
+        });
+    }
+    pub fn update(&mut self, system: &mut SystemDefinition) {
+        system.wrap_atom_positions();
+        self.updates += 1;
+        match self.needs_rebuild(system) {
+            true => self.rebuild(system),
+            false => self.refresh(system),
+        }
+    }
+    pub fn summary(&self) -> String {
+        let average_interval = match self.rebuilds {
+            0 => 0.0,
+            _ => self.updates as f64 / self.rebuilds as f64,
+        };
+        format!(
+            "Neighbors list rebuilt {} times over {} updates (every {:.2} updates on average)",
+            self.rebuilds, self.updates, average_interval
+        )
+    }
+    // Only pairs within the cutoff are returned, the ones inside the skin are kept for later steps
     pub fn get_neighbors(&self, index: u64) -> Vec<NeighborsListEntry> {
         match self.neighbors.get(&(index as u64)) {
             None => {
                 println!("No neighbors for index {}", index);
                 vec![]
             }
-            Some(neighbors) => neighbors.clone(),
+            Some(neighbors) => neighbors
+                .iter()
+                .filter(|entry| entry.distance < self.cutoff)
+                .cloned()
+                .collect(),
         }
     }
 }
@@ -114,3 +291,125 @@ impl std::fmt::Display for NeighborsList {
         write!(f, "{:?}", self.neighbors)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use yaml_rust::YamlLoader;
+
+    use super::*;
+    use crate::system::atom::Atom;
+
+    // Random atoms in the given box, spilling out of it along non-periodic directions
+    fn random_system(cell: &str, periodicity: &str, count: usize) -> SystemDefinition {
+        let definition = YamlLoader::load_from_str(&format!(
+            "
+            cell: {}
+            atoms:
+              - {{name: Ar, position: [0.0, 0.0, 0.0]}}
+            periodicity: {}
+            units: atomic
+            ",
+            cell, periodicity
+        ))
+        .unwrap();
+        let mut system = SystemDefinition::from(&definition[0]);
+        let mut rng = StdRng::seed_from_u64(11);
+        system.atoms = (0..count)
+            .map(|_| {
+                let fractional_position =
+                    Vector3::from_fn(|i, _| match system.simulation_box.periodicity[i] {
+                        true => rng.gen_range(0.0..1.0),
+                        false => rng.gen_range(-0.2..1.2),
+                    });
+                let mut atom = Atom::new();
+                atom.current.position = system.simulation_box.to_cartesian(&fractional_position);
+                atom
+            })
+            .collect();
+        system
+    }
+
+    fn build(system: &SystemDefinition, method: &str, cutoff: f64, half: bool) -> NeighborsList {
+        let settings = YamlLoader::load_from_str(&format!(
+            "{{cutoff: {:.2}, log: false, method: {}, half: {}}}",
+            cutoff, method, half
+        ))
+        .unwrap();
+        let mut neighbors_list = NeighborsList::from(&settings[0]);
+        neighbors_list.rebuild(system);
+        neighbors_list
+    }
+
+    fn assert_same_lists(system: &SystemDefinition, cutoff: f64, expected_cells: [usize; 3]) {
+        let widths = system.simulation_box.perpendicular_widths();
+        let cells_count = [0, 1, 2].map(|i| (widths[i] / cutoff).floor() as usize);
+        assert_eq!(cells_count, expected_cells);
+        for half in [false, true] {
+            let brute_force = build(system, "brute_force", cutoff, half);
+            let linked_cells = build(system, "linked_cells", cutoff, half);
+            let mut pairs = 0;
+            for index in 0..system.atoms.len() as u64 {
+                let expected = &brute_force.neighbors[&index];
+                let found = &linked_cells.neighbors[&index];
+                assert_eq!(
+                    found.iter().map(|entry| entry.index).collect::<Vec<u64>>(),
+                    expected
+                        .iter()
+                        .map(|entry| entry.index)
+                        .collect::<Vec<u64>>(),
+                    "neighbors of atom {}",
+                    index
+                );
+                for (found_entry, expected_entry) in found.iter().zip(expected.iter()) {
+                    assert_eq!(found_entry.distance, expected_entry.distance);
+                }
+                pairs += expected.len();
+            }
+            assert!(pairs > 0);
+        }
+    }
+
+    #[test]
+    fn linked_cells_match_brute_force_in_cubic_box() {
+        let system = random_system("[[12.0, 0, 0], [0, 12.0, 0], [0, 0, 12.0]]", "xyz", 300);
+        assert_same_lists(&system, 3.5, [3, 3, 3]);
+        // Two cells per direction, where the stencil wraps onto itself
+        assert_same_lists(&system, 5.0, [2, 2, 2]);
+    }
+
+    #[test]
+    fn linked_cells_match_brute_force_in_triclinic_box() {
+        let system = random_system(
+            "[[12.0, 0, 0], [5.0, 11.0, 0], [-4.0, 3.0, 13.0]]",
+            "xyz",
+            300,
+        );
+        assert_same_lists(&system, 2.9, [3, 3, 4]);
+        assert_same_lists(&system, 4.5, [2, 2, 2]);
+    }
+
+    #[test]
+    fn linked_cells_match_brute_force_with_non_periodic_direction() {
+        let system = random_system("[[12.0, 0, 0], [0, 12.0, 0], [0, 0, 7.0]]", "xy", 200);
+        assert_same_lists(&system, 3.5, [3, 3, 2]);
+        // A single cell along the non-periodic direction, which has no cutoff restriction
+        assert_same_lists(&system, 5.0, [2, 2, 1]);
+    }
+
+    // The second vector is almost ten long but its periodic images are only three apart
+    #[test]
+    #[should_panic(expected = "exceeds half of the box width")]
+    fn cutoff_beyond_half_of_skewed_width() {
+        let system = random_system("[[10.0, 0, 0], [9.0, 3.0, 0], [0, 0, 10.0]]", "xyz", 10);
+        build(&system, "linked_cells", 2.0, false);
+    }
+
+    #[test]
+    fn cutoff_beyond_half_of_non_periodic_width() {
+        let system = random_system("[[10.0, 0, 0], [0, 10.0, 0], [0, 0, 3.0]]", "xy", 10);
+        let neighbors_list = build(&system, "linked_cells", 2.0, false);
+        assert_eq!(neighbors_list.rebuilds, 1);
+    }
+}
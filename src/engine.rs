@@ -1,3 +1,4 @@
+use crate::dynamics::evaluate_forces;
 use crate::simulation::Simulation;
 
 use crate::io::input::parse_yaml;
@@ -15,34 +16,50 @@ impl SimulationRunnerEngine {
             "yaml" => parse_yaml(script_filepath),
             _ => panic!("Unknown file extension"),
         };
+        let simulation = Simulation::from(&script);
         let sim = SimulationRunnerEngine {
-            simulation: Simulation::from(&script),
             logger: match &script["logger"] {
                 yaml_rust::Yaml::BadValue => SimulationLogger::default(),
-                _ => SimulationLogger::from(&script["logger"]),
+                _ => SimulationLogger::from(&script["logger"], &simulation.potential_model),
             },
+            simulation,
         };
         return sim;
     }
 
+    pub fn minimize(&mut self) {
+        if let Some(mut minimizer) = self.simulation.minimizer.take() {
+            minimizer.run(&mut self.simulation, &self.logger);
+            self.simulation.minimizer = Some(minimizer);
+        }
+    }
+
     pub fn run(&mut self) -> () {
-        if self.simulation.clock.current_step > 1 {
+        self.minimize();
+        if self.simulation.clock.current_step != 1 {
             self.simulation.clock.reset();
         }
-        self.simulation
-            .neighbors
-            .update(&mut self.simulation.system);
+        // Forces of the starting configuration are needed by the first half kick
+        evaluate_forces(
+            &mut self.simulation.system,
+            &self.simulation.potential_model,
+            &mut self.simulation.neighbors,
+        );
         while !self.simulation.clock.has_finished() {
+            self.simulation
+                .thermodynamics
+                .prepare(&mut self.simulation.system, self.simulation.clock.timestep);
             self.simulation.integrator.next_step(
-                &mut self.simulation.system.atoms,
+                &mut self.simulation.system,
                 &self.simulation.potential_model,
                 &mut self.simulation.neighbors,
-                &self.simulation.system.units,
             );
             self.simulation
-                .neighbors
-                .update(&mut self.simulation.system);
-            self.simulation.thermodynamics.update();
+                .thermodynamics
+                .update(&mut self.simulation.system, self.simulation.clock.timestep);
+            self.simulation
+                .energetics
+                .update(&mut self.simulation.system, &self.simulation.thermodynamics);
             self.logger.log_simulation_state(&self.simulation);
             if self.simulation.neighbors.log {
                 self.logger
@@ -50,5 +67,6 @@ impl SimulationRunnerEngine {
             }
             self.simulation.clock.tick();
         }
+        println!("{}", self.simulation.neighbors.summary());
     }
 }
@@ -12,6 +12,15 @@ pub fn parse_yaml(filepath: &str) -> yaml_rust::Yaml {
     script_yaml[0].clone()
 }
 
+pub fn to_f64(yaml: &yaml_rust::Yaml) -> f64 {
+    match yaml {
+        Yaml::Real(x) => x.parse::<f64>().unwrap(),
+        Yaml::String(x) => x.parse::<f64>().unwrap(),
+        Yaml::Integer(x) => *x as f64,
+        _ => panic!("Expected a number, found {:?}", yaml),
+    }
+}
+
 pub fn to_vec_f64<const SIZE: usize>(yaml: &yaml_rust::Yaml) -> [f64; SIZE] {
     let vectorized_yaml_entry: &Vec<Yaml> = yaml.as_vec().unwrap();
     if vectorized_yaml_entry.len() != SIZE {
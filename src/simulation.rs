@@ -3,10 +3,12 @@ use core::fmt;
 use rayon::prelude::*;
 use yaml_rust::Yaml;
 
+use crate::dynamics::integrators::langevin::LangevinIntegrator;
 use crate::dynamics::integrators::verlet::VerletIntegrator;
 use crate::dynamics::neighbors::NeighborsList;
 use crate::dynamics::DynamicsIntegrator;
 use crate::statics::energetics::SystemEnergetics;
+use crate::statics::minimizers::Minimizer;
 use crate::statics::models::PotentialModel;
 use crate::system::SystemDefinition;
 use crate::thermodynamics::Thermodynamics;
@@ -51,6 +53,7 @@ pub struct Simulation {
     pub neighbors: NeighborsList,
     pub energetics: SystemEnergetics,
     pub thermodynamics: Thermodynamics,
+    pub minimizer: Option<Minimizer>,
 }
 
 impl Simulation {
@@ -65,17 +68,45 @@ impl Simulation {
             _ => dynamics_setup["steps"].as_i64().unwrap() as f64 * timestep,
         };
 
+        let mut system = SystemDefinition::from(system_definition);
+        let integrator = match dynamics_setup["integrator"]["type"].as_str().unwrap() {
+            "verlet" => DynamicsIntegrator::Verlet(VerletIntegrator::from(dynamics_setup, &system)),
+            "langevin" => {
+                DynamicsIntegrator::Langevin(Box::new(LangevinIntegrator::from(dynamics_setup)))
+            }
+            _ => panic!("Unknown integrator"),
+        };
+        let mut thermodynamics = Thermodynamics::from(&yaml["thermodynamics"]);
+        thermodynamics.conserves_momentum = integrator.conserves_momentum();
+        if let Some(constraints) = integrator.constraints() {
+            constraints.initialize(&mut system);
+            thermodynamics.constrained_degrees_of_freedom = constraints.count();
+        }
+        thermodynamics.initialize(&mut system, integrator.constraints());
+
+        let mut potential_model = PotentialModel::from(&yaml["potential"]);
+        potential_model.initialize(&system);
+        let neighbors = NeighborsList::from(&yaml["neighbors"]);
+        if potential_model.max_cutoff() > neighbors.cutoff() {
+            panic!(
+                "Potential cutoff ({}) exceeds the neighbors list cutoff ({})",
+                potential_model.max_cutoff(),
+                neighbors.cutoff()
+            );
+        }
+
         Simulation {
-            system: SystemDefinition::from(system_definition),
-            potential_model: PotentialModel::from(&yaml["potential"]),
-            integrator: match dynamics_setup["integrator"]["type"].as_str().unwrap() {
-                "verlet" => DynamicsIntegrator::Verlet(VerletIntegrator::from(&dynamics_setup)),
-                _ => panic!("Unknown integrator"),
-            },
+            system,
+            potential_model,
+            integrator,
             clock: InternalClock::new(timestep, calculated_total_time),
-            neighbors: NeighborsList::from(&yaml["neighbors"]),
+            neighbors,
             energetics: SystemEnergetics::new(),
-            thermodynamics: Thermodynamics::from(&yaml["thermodynamics"]),
+            thermodynamics,
+            minimizer: match &yaml["minimize"] {
+                Yaml::BadValue => None,
+                minimization_setup => Some(Minimizer::from(minimization_setup, timestep)),
+            },
         }
     }
 }
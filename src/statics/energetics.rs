@@ -1,8 +1,56 @@
+use nalgebra::Matrix3;
+use rayon::prelude::*;
+
+use crate::system::atom::Atom;
+use crate::system::SystemDefinition;
+use crate::thermodynamics::Thermodynamics;
+use crate::utils::metrics::UnitSystem;
+
 pub struct SystemEnergetics {
     pub potential_energy: f64,
     pub kinetic_energy: f64,
     pub total_energy: f64,
+    pub conserved_energy: f64,
     pub temperature: f64,
+    pub max_force: f64,
+    pub pressure: f64,
+    pub pressure_tensor: Matrix3<f64>,
+}
+
+pub fn calculate_temperature(
+    kinetic_energy: f64,
+    degrees_of_freedom: usize,
+    unit_system: &UnitSystem,
+) -> f64 {
+    match degrees_of_freedom {
+        0 => 0.0,
+        _ => 2.0 * kinetic_energy / (degrees_of_freedom as f64 * unit_system.boltzmann_constant()),
+    }
+}
+
+pub fn calculate_kinetic_energy(atoms: &[Atom], unit_system: &UnitSystem) -> f64 {
+    atoms
+        .par_iter()
+        .map(|atom| 0.5 * atom.mass * atom.current.velocity.norm_squared())
+        .sum::<f64>()
+        * unit_system.kinetic_energy_factor()
+}
+
+// P = (sum m v (x) v + W) / V in pressure units, with the virial W already split between pair members
+pub fn calculate_pressure_tensor(system: &SystemDefinition) -> Matrix3<f64> {
+    let kinetic_tensor = system
+        .atoms
+        .par_iter()
+        .map(|atom| atom.mass * atom.current.velocity * atom.current.velocity.transpose())
+        .reduce(Matrix3::zeros, |a, b| a + b)
+        * system.units.kinetic_energy_factor();
+    let virial_tensor = system
+        .atoms
+        .par_iter()
+        .map(|atom| atom.current.virial)
+        .reduce(Matrix3::zeros, |a, b| a + b);
+    (kinetic_tensor + virial_tensor)
+        * (system.units.pressure_factor() / system.simulation_box.volume())
 }
 
 impl SystemEnergetics {
@@ -11,7 +59,43 @@ impl SystemEnergetics {
             potential_energy: 0.0,
             kinetic_energy: 0.0,
             total_energy: 0.0,
+            conserved_energy: 0.0,
             temperature: 0.0,
+            max_force: 0.0,
+            pressure: 0.0,
+            pressure_tensor: Matrix3::zeros(),
         }
     }
+    pub fn update(&mut self, system: &mut SystemDefinition, thermodynamics: &Thermodynamics) {
+        let degrees_of_freedom = thermodynamics.degrees_of_freedom(system.atoms.len());
+        let kinetic_energy_factor = system.units.kinetic_energy_factor();
+        system.atoms.par_iter_mut().for_each(|atom| {
+            atom.current.kinetic_energy =
+                0.5 * atom.mass * atom.current.velocity.norm_squared() * kinetic_energy_factor;
+            atom.current.total_energy = atom.current.kinetic_energy + atom.current.potential_energy;
+        });
+        // Per-atom potential energies already hold half of each pair contribution
+        self.potential_energy = system
+            .atoms
+            .par_iter()
+            .map(|atom| atom.current.potential_energy)
+            .sum();
+        self.kinetic_energy = system
+            .atoms
+            .par_iter()
+            .map(|atom| atom.current.kinetic_energy)
+            .sum();
+        self.total_energy = self.potential_energy + self.kinetic_energy;
+        self.conserved_energy = self.total_energy + thermodynamics.reservoir_energy(system);
+        self.temperature =
+            calculate_temperature(self.kinetic_energy, degrees_of_freedom, &system.units);
+
+        self.max_force = system
+            .atoms
+            .par_iter()
+            .map(|atom| atom.current.force.norm())
+            .reduce(|| 0.0, f64::max);
+        self.pressure_tensor = calculate_pressure_tensor(system);
+        self.pressure = self.pressure_tensor.trace() / 3.0;
+    }
 }
@@ -0,0 +1,93 @@
+use nalgebra::Vector3;
+
+use crate::simulation::Simulation;
+use crate::statics::minimizers::{
+    collect_forces, collect_positions, displace_atoms, dot, evaluate_forces, max_norm,
+};
+
+const STEP_INCREASE: f64 = 1.2;
+const STEP_DECREASE: f64 = 0.5;
+const MAX_TRIALS: usize = 20;
+
+// Polak-Ribiere conjugate gradient with a backtracking line search along the search direction
+pub struct ConjugateGradientMinimizer {
+    pub step_size: f64,
+    previous_forces: Vec<Vector3<f64>>,
+    direction: Vec<Vector3<f64>>,
+}
+
+impl ConjugateGradientMinimizer {
+    pub fn new(step_size: f64) -> ConjugateGradientMinimizer {
+        ConjugateGradientMinimizer {
+            step_size,
+            previous_forces: Vec::new(),
+            direction: Vec::new(),
+        }
+    }
+
+    fn update_direction(&mut self, forces: Vec<Vector3<f64>>) {
+        let restart = self.previous_forces.len() != forces.len();
+        let beta = match restart {
+            true => 0.0,
+            false => {
+                let difference = forces
+                    .iter()
+                    .zip(self.previous_forces.iter())
+                    .map(|(current, previous)| current - previous)
+                    .collect::<Vec<Vector3<f64>>>();
+                let normalization = dot(&self.previous_forces, &self.previous_forces);
+                match normalization > 0.0 {
+                    true => (dot(&forces, &difference) / normalization).max(0.0),
+                    false => 0.0,
+                }
+            }
+        };
+        self.direction = match beta == 0.0 {
+            true => forces.clone(),
+            false => forces
+                .iter()
+                .zip(self.direction.iter())
+                .map(|(force, direction)| force + beta * direction)
+                .collect(),
+        };
+        // Fall back to steepest descent whenever the direction stops pointing downhill
+        if dot(&self.direction, &forces) <= 0.0 {
+            self.direction = forces.clone();
+        }
+        self.previous_forces = forces;
+    }
+
+    pub fn step(&mut self, simulation: &mut Simulation) -> Option<f64> {
+        let initial_energy = simulation.energetics.potential_energy;
+        let initial_positions = collect_positions(&simulation.system);
+        self.update_direction(collect_forces(&simulation.system.atoms));
+        let max_displacement = max_norm(&self.direction);
+        if max_displacement == 0.0 {
+            return Some(initial_energy);
+        }
+        for _ in 0..MAX_TRIALS {
+            displace_atoms(
+                &mut simulation.system,
+                &initial_positions,
+                &self.direction,
+                self.step_size / max_displacement,
+            );
+            let energy = evaluate_forces(simulation);
+            if energy < initial_energy {
+                self.step_size *= STEP_INCREASE;
+                return Some(energy);
+            }
+            self.step_size *= STEP_DECREASE;
+        }
+        // The line search failed, so restart from the steepest descent direction next time
+        self.previous_forces.clear();
+        displace_atoms(
+            &mut simulation.system,
+            &initial_positions,
+            &self.direction,
+            0.0,
+        );
+        evaluate_forces(simulation);
+        None
+    }
+}
@@ -0,0 +1,92 @@
+use nalgebra::Vector3;
+use rayon::prelude::*;
+use yaml_rust::Yaml;
+
+use crate::dynamics::acceleration_factor;
+use crate::io::input::to_f64;
+use crate::simulation::Simulation;
+use crate::statics::minimizers::{dot, evaluate_forces};
+
+const DELAY_STEPS: usize = 5;
+const TIMESTEP_INCREASE: f64 = 1.1;
+const TIMESTEP_DECREASE: f64 = 0.5;
+const MIXING_START: f64 = 0.1;
+const MIXING_DECREASE: f64 = 0.99;
+const MAX_TIMESTEP_RATIO: f64 = 10.0;
+
+// Fast inertial relaxation engine (Bitzek, Koskinen, Gahler, Moseler and Gumbsch, 2006)
+pub struct FireMinimizer {
+    pub timestep: f64,
+    pub max_timestep: f64,
+    mixing: f64,
+    steps_since_reset: usize,
+    velocities: Vec<Vector3<f64>>,
+}
+
+impl FireMinimizer {
+    pub fn from(yaml: &Yaml, timestep: f64) -> FireMinimizer {
+        let timestep = match &yaml["timestep"] {
+            Yaml::BadValue => timestep,
+            x => to_f64(x),
+        };
+        FireMinimizer {
+            timestep,
+            max_timestep: match &yaml["max_timestep"] {
+                Yaml::BadValue => MAX_TIMESTEP_RATIO * timestep,
+                x => to_f64(x),
+            },
+            mixing: MIXING_START,
+            steps_since_reset: 0,
+            velocities: Vec::new(),
+        }
+    }
+
+    pub fn step(&mut self, simulation: &mut Simulation) -> f64 {
+        let atoms = &mut simulation.system.atoms;
+        if self.velocities.len() != atoms.len() {
+            self.velocities = vec![Vector3::zeros(); atoms.len()];
+        }
+        let acceleration_factor = acceleration_factor(&simulation.system.units);
+        let forces = atoms
+            .par_iter()
+            .map(|atom| atom.current.force)
+            .collect::<Vec<Vector3<f64>>>();
+
+        let power = dot(&forces, &self.velocities);
+        if power > 0.0 {
+            let velocity_norm = dot(&self.velocities, &self.velocities).sqrt();
+            let force_norm = dot(&forces, &forces).sqrt();
+            let mixing = self.mixing;
+            self.velocities
+                .par_iter_mut()
+                .zip(forces.par_iter())
+                .for_each(|(velocity, force)| {
+                    *velocity =
+                        (1.0 - mixing) * *velocity + mixing * velocity_norm * force / force_norm;
+                });
+            self.steps_since_reset += 1;
+            if self.steps_since_reset > DELAY_STEPS {
+                self.timestep = (self.timestep * TIMESTEP_INCREASE).min(self.max_timestep);
+                self.mixing *= MIXING_DECREASE;
+            }
+        } else {
+            self.velocities
+                .par_iter_mut()
+                .for_each(|velocity| *velocity = Vector3::zeros());
+            self.steps_since_reset = 0;
+            self.timestep *= TIMESTEP_DECREASE;
+            self.mixing = MIXING_START;
+        }
+
+        // Semi-implicit Euler step with the atomic masses
+        let timestep = self.timestep;
+        atoms
+            .par_iter_mut()
+            .zip(self.velocities.par_iter_mut())
+            .for_each(|(atom, velocity)| {
+                *velocity += timestep * acceleration_factor * atom.current.force / atom.mass;
+                atom.current.position += timestep * *velocity;
+            });
+        evaluate_forces(simulation)
+    }
+}
@@ -0,0 +1,212 @@
+pub mod conjugate_gradient;
+pub mod fire;
+pub mod steepest_descent;
+
+use nalgebra::Vector3;
+use rayon::prelude::*;
+use yaml_rust::Yaml;
+
+use crate::dynamics;
+use crate::io::input::to_f64;
+use crate::simulation::Simulation;
+use crate::system::atom::Atom;
+use crate::system::SystemDefinition;
+use crate::utils::logger::SimulationLogger;
+
+const DEFAULT_ENERGY_TOLERANCE: f64 = 1e-8;
+const DEFAULT_FORCE_TOLERANCE: f64 = 1e-4;
+const DEFAULT_MAX_ITERATIONS: u64 = 1000;
+const DEFAULT_STEP_SIZE: f64 = 0.01;
+
+pub struct MinimizationCriteria {
+    pub energy_tolerance: f64, // Relative change of the energy between iterations
+    pub force_tolerance: f64,  // Largest force acting on a single atom
+    pub max_iterations: u64,
+}
+
+impl MinimizationCriteria {
+    pub fn from(yaml: &Yaml) -> MinimizationCriteria {
+        MinimizationCriteria {
+            energy_tolerance: match &yaml["energy_tolerance"] {
+                Yaml::BadValue => DEFAULT_ENERGY_TOLERANCE,
+                x => to_f64(x),
+            },
+            force_tolerance: match &yaml["force_tolerance"] {
+                Yaml::BadValue => DEFAULT_FORCE_TOLERANCE,
+                x => to_f64(x),
+            },
+            max_iterations: match &yaml["max_iterations"] {
+                Yaml::Integer(x) if *x > 0 => *x as u64,
+                Yaml::BadValue => DEFAULT_MAX_ITERATIONS,
+                _ => panic!("Maximum number of iterations must be a positive integer"),
+            },
+        }
+    }
+    fn energy_converged(&self, previous_energy: f64, energy: f64) -> bool {
+        (energy - previous_energy).abs()
+            <= self.energy_tolerance * 0.5 * (energy.abs() + previous_energy.abs() + f64::EPSILON)
+    }
+}
+
+pub enum MinimizationAlgorithm {
+    SteepestDescent(steepest_descent::SteepestDescentMinimizer),
+    ConjugateGradient(conjugate_gradient::ConjugateGradientMinimizer),
+    Fire(fire::FireMinimizer),
+}
+
+impl MinimizationAlgorithm {
+    pub fn from(yaml: &Yaml, timestep: f64) -> MinimizationAlgorithm {
+        let step_size = match &yaml["step_size"] {
+            Yaml::BadValue => DEFAULT_STEP_SIZE,
+            x => to_f64(x),
+        };
+        match yaml["algorithm"].as_str() {
+            Some("steepest_descent") | Some("sd") => MinimizationAlgorithm::SteepestDescent(
+                steepest_descent::SteepestDescentMinimizer::new(step_size),
+            ),
+            Some("conjugate_gradient") | Some("cg") => MinimizationAlgorithm::ConjugateGradient(
+                conjugate_gradient::ConjugateGradientMinimizer::new(step_size),
+            ),
+            Some("fire") => MinimizationAlgorithm::Fire(fire::FireMinimizer::from(yaml, timestep)),
+            _ => panic!("Unknown minimization algorithm"),
+        }
+    }
+
+    // Performs a single iteration, leaving forces evaluated at the new positions. Returns the
+    // new energy, or None when the line search found no lower energy and nothing moved.
+    fn step(&mut self, simulation: &mut Simulation) -> Option<f64> {
+        match self {
+            MinimizationAlgorithm::SteepestDescent(x) => x.step(simulation),
+            MinimizationAlgorithm::ConjugateGradient(x) => x.step(simulation),
+            MinimizationAlgorithm::Fire(x) => Some(x.step(simulation)),
+        }
+    }
+}
+
+impl std::fmt::Display for MinimizationAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MinimizationAlgorithm::SteepestDescent(_) => write!(f, "steepest descent"),
+            MinimizationAlgorithm::ConjugateGradient(_) => {
+                write!(f, "Polak-Ribiere conjugate gradient")
+            }
+            MinimizationAlgorithm::Fire(_) => write!(f, "FIRE"),
+        }
+    }
+}
+
+pub struct Minimizer {
+    pub algorithm: MinimizationAlgorithm,
+    pub criteria: MinimizationCriteria,
+}
+
+impl Minimizer {
+    pub fn from(yaml: &Yaml, timestep: f64) -> Minimizer {
+        Minimizer {
+            algorithm: MinimizationAlgorithm::from(yaml, timestep),
+            criteria: MinimizationCriteria::from(yaml),
+        }
+    }
+
+    pub fn run(&mut self, simulation: &mut Simulation, logger: &SimulationLogger) {
+        let criteria = &self.criteria;
+        println!("Minimizing energy with {}", self.algorithm);
+        let mut energy = evaluate_forces(simulation);
+        simulation.clock.current_step = 0;
+        logger.log_simulation_state(simulation);
+        for iteration in 1..=criteria.max_iterations {
+            if simulation.energetics.max_force <= criteria.force_tolerance {
+                println!(
+                    "Minimization converged on forces after {} iterations",
+                    iteration - 1
+                );
+                return;
+            }
+            let previous_energy = energy;
+            // A failed line search is retried once, conjugate gradients restarting from the
+            // steepest descent direction
+            energy = match self
+                .algorithm
+                .step(simulation)
+                .or_else(|| self.algorithm.step(simulation))
+            {
+                Some(x) => x,
+                None => {
+                    println!(
+                        "Minimization stopped after {} iterations: line search failed",
+                        iteration - 1
+                    );
+                    return;
+                }
+            };
+            simulation.clock.current_step = iteration;
+            logger.log_simulation_state(simulation);
+            if criteria.energy_converged(previous_energy, energy) {
+                println!(
+                    "Minimization converged on energy after {} iterations",
+                    iteration
+                );
+                return;
+            }
+        }
+        println!(
+            "Minimization stopped after reaching {} iterations",
+            criteria.max_iterations
+        );
+    }
+}
+
+// Rebuilds the neighbors list and forces for the current positions, returning the potential energy
+pub fn evaluate_forces(simulation: &mut Simulation) -> f64 {
+    dynamics::evaluate_forces(
+        &mut simulation.system,
+        &simulation.potential_model,
+        &mut simulation.neighbors,
+    );
+    simulation
+        .energetics
+        .update(&mut simulation.system, &simulation.thermodynamics);
+    simulation.energetics.potential_energy
+}
+
+// Positions are unwrapped so that displacements stay meaningful across periodic boundaries
+pub fn collect_positions(system: &SystemDefinition) -> Vec<Vector3<f64>> {
+    system
+        .atoms
+        .par_iter()
+        .map(|atom| system.unwrapped_position(atom))
+        .collect()
+}
+
+pub fn collect_forces(atoms: &[Atom]) -> Vec<Vector3<f64>> {
+    atoms.par_iter().map(|atom| atom.current.force).collect()
+}
+
+// Moves every atom from its reference position along the given direction
+pub fn displace_atoms(
+    system: &mut SystemDefinition,
+    reference: &[Vector3<f64>],
+    direction: &[Vector3<f64>],
+    length: f64,
+) {
+    system
+        .atoms
+        .par_iter_mut()
+        .zip(reference.par_iter().zip(direction.par_iter()))
+        .for_each(|(atom, (position, displacement))| {
+            // The reference is unwrapped, so the atom starts over from the original image
+            atom.current.position = position + length * displacement;
+            atom.image = [0, 0, 0];
+        });
+}
+
+pub fn dot(a: &[Vector3<f64>], b: &[Vector3<f64>]) -> f64 {
+    a.par_iter().zip(b.par_iter()).map(|(x, y)| x.dot(y)).sum()
+}
+
+pub fn max_norm(vectors: &[Vector3<f64>]) -> f64 {
+    vectors
+        .par_iter()
+        .map(|x| x.norm())
+        .reduce(|| 0.0, f64::max)
+}
@@ -0,0 +1,47 @@
+use crate::simulation::Simulation;
+use crate::statics::minimizers::{
+    collect_forces, collect_positions, displace_atoms, evaluate_forces, max_norm,
+};
+
+const STEP_INCREASE: f64 = 1.2;
+const STEP_DECREASE: f64 = 0.5;
+const MAX_TRIALS: usize = 20;
+
+// Moves along the forces with the largest atomic displacement limited to the step size,
+// which grows after every accepted step and shrinks whenever the energy goes up
+pub struct SteepestDescentMinimizer {
+    pub step_size: f64,
+}
+
+impl SteepestDescentMinimizer {
+    pub fn new(step_size: f64) -> SteepestDescentMinimizer {
+        SteepestDescentMinimizer { step_size }
+    }
+
+    pub fn step(&mut self, simulation: &mut Simulation) -> Option<f64> {
+        let initial_energy = simulation.energetics.potential_energy;
+        let initial_positions = collect_positions(&simulation.system);
+        let forces = collect_forces(&simulation.system.atoms);
+        let max_force = max_norm(&forces);
+        if max_force == 0.0 {
+            return Some(initial_energy);
+        }
+        for _ in 0..MAX_TRIALS {
+            displace_atoms(
+                &mut simulation.system,
+                &initial_positions,
+                &forces,
+                self.step_size / max_force,
+            );
+            let energy = evaluate_forces(simulation);
+            if energy < initial_energy {
+                self.step_size *= STEP_INCREASE;
+                return Some(energy);
+            }
+            self.step_size *= STEP_DECREASE;
+        }
+        displace_atoms(&mut simulation.system, &initial_positions, &forces, 0.0);
+        evaluate_forces(simulation);
+        None
+    }
+}
@@ -1,2 +1,3 @@
 pub mod energetics;
+pub mod minimizers;
 pub mod models;
@@ -0,0 +1,559 @@
+use std::collections::HashMap;
+
+use nalgebra::{Matrix3, Vector3};
+use rayon::prelude::*;
+use yaml_rust::Yaml;
+
+use crate::io::input::to_f64;
+use crate::statics::models::PairContributions;
+use crate::system::topology::{BondedGroup, Topology};
+use crate::system::SystemDefinition;
+
+// Factors applied to pair interactions by bond separation: unbonded, 1-2, 1-3 and 1-4 pairs
+pub type SpecialFactors = [f64; 4];
+
+pub const UNSCALED: SpecialFactors = [1.0; 4];
+
+// Scaling of the nonbonded interactions between atoms close along the bonds, read from
+//   special_bonds: {short_range: [f12, f13, f14], coulomb: [f12, f13, f14]}
+// Pairs closer than 1-4 are excluded by default, as in LAMMPS.
+pub struct SpecialBonds {
+    pub short_range: SpecialFactors,
+    pub coulomb: SpecialFactors,
+}
+
+impl SpecialBonds {
+    pub fn from(yaml: &Yaml) -> SpecialBonds {
+        let factors = |yaml: &Yaml| match yaml {
+            Yaml::BadValue => [1.0, 0.0, 0.0, 0.0],
+            Yaml::Array(x) if x.len() == 3 => [1.0, to_f64(&x[0]), to_f64(&x[1]), to_f64(&x[2])],
+            _ => panic!("Special bonds factors must list the 1-2, 1-3 and 1-4 factors"),
+        };
+        SpecialBonds {
+            short_range: factors(&yaml["short_range"]),
+            coulomb: factors(&yaml["coulomb"]),
+        }
+    }
+}
+
+pub enum BondStyle {
+    Harmonic { k: f64, r0: f64 },              // K (r - r0)^2
+    Morse { depth: f64, alpha: f64, r0: f64 }, // D (1 - exp(-alpha (r - r0)))^2
+}
+
+impl BondStyle {
+    fn from(yaml: &Yaml) -> BondStyle {
+        match yaml["style"].as_str() {
+            Some("harmonic") => BondStyle::Harmonic {
+                k: to_f64(&yaml["k"]),
+                r0: to_f64(&yaml["r0"]),
+            },
+            Some("morse") => BondStyle::Morse {
+                depth: to_f64(&yaml["depth"]),
+                alpha: to_f64(&yaml["alpha"]),
+                r0: to_f64(&yaml["r0"]),
+            },
+            _ => panic!("Unknown bond style"),
+        }
+    }
+    // Energy and its derivative with respect to the bond length
+    fn evaluate(&self, r: f64) -> (f64, f64) {
+        match self {
+            BondStyle::Harmonic { k, r0 } => (k * (r - r0).powi(2), 2.0 * k * (r - r0)),
+            BondStyle::Morse { depth, alpha, r0 } => {
+                let exponential = (-alpha * (r - r0)).exp();
+                (
+                    depth * (1.0 - exponential).powi(2),
+                    2.0 * depth * alpha * exponential * (1.0 - exponential),
+                )
+            }
+        }
+    }
+}
+
+// Angles are given in degrees
+pub enum AngleStyle {
+    Harmonic { k: f64, theta0: f64 }, // K (theta - theta0)^2
+    Cosine { k: f64 },                // K (1 + cos(theta))
+}
+
+impl AngleStyle {
+    fn from(yaml: &Yaml) -> AngleStyle {
+        match yaml["style"].as_str() {
+            Some("harmonic") => AngleStyle::Harmonic {
+                k: to_f64(&yaml["k"]),
+                theta0: to_f64(&yaml["theta0"]).to_radians(),
+            },
+            Some("cosine") => AngleStyle::Cosine {
+                k: to_f64(&yaml["k"]),
+            },
+            _ => panic!("Unknown angle style"),
+        }
+    }
+    // Energy and its derivative with respect to the cosine of the angle
+    fn evaluate(&self, cosine: f64) -> (f64, f64) {
+        match self {
+            AngleStyle::Harmonic { k, theta0 } => {
+                let theta = cosine.acos();
+                // Kept away from zero so that straight angles stay finite
+                let sine = theta.sin().max(1e-3);
+                (
+                    k * (theta - theta0).powi(2),
+                    -2.0 * k * (theta - theta0) / sine,
+                )
+            }
+            AngleStyle::Cosine { k } => (k * (1.0 + cosine), *k),
+        }
+    }
+}
+
+pub enum DihedralStyle {
+    // 1/2 K1 (1 + cos(phi)) + 1/2 K2 (1 - cos(2 phi)) + 1/2 K3 (1 + cos(3 phi))
+    //   + 1/2 K4 (1 - cos(4 phi))
+    Opls { k: [f64; 4] },
+    // K (1 + cos(n phi - d)), with d in degrees
+    Charmm { k: f64, n: i32, d: f64 },
+}
+
+impl DihedralStyle {
+    fn from(yaml: &Yaml) -> DihedralStyle {
+        match yaml["style"].as_str() {
+            Some("opls") => DihedralStyle::Opls {
+                k: match yaml["k"].as_vec() {
+                    Some(k) if k.len() == 4 => [0, 1, 2, 3].map(|i| to_f64(&k[i])),
+                    _ => panic!("OPLS dihedrals require four coefficients k"),
+                },
+            },
+            Some("charmm") => DihedralStyle::Charmm {
+                k: to_f64(&yaml["k"]),
+                n: match yaml["n"].as_i64() {
+                    Some(n) if n >= 0 => n as i32,
+                    _ => panic!("CHARMM dihedral multiplicity must be a non-negative integer"),
+                },
+                d: to_f64(&yaml["d"]).to_radians(),
+            },
+            _ => panic!("Unknown dihedral style"),
+        }
+    }
+    // Energy and its derivative with respect to the dihedral angle
+    fn evaluate(&self, phi: f64) -> (f64, f64) {
+        match self {
+            DihedralStyle::Opls { k } => (
+                0.5 * (k[0] * (1.0 + phi.cos())
+                    + k[1] * (1.0 - (2.0 * phi).cos())
+                    + k[2] * (1.0 + (3.0 * phi).cos())
+                    + k[3] * (1.0 - (4.0 * phi).cos())),
+                0.5 * (-k[0] * phi.sin() + 2.0 * k[1] * (2.0 * phi).sin()
+                    - 3.0 * k[2] * (3.0 * phi).sin()
+                    + 4.0 * k[3] * (4.0 * phi).sin()),
+            ),
+            DihedralStyle::Charmm { k, n, d } => {
+                let angle = *n as f64 * phi - d;
+                (k * (1.0 + angle.cos()), -k * *n as f64 * angle.sin())
+            }
+        }
+    }
+}
+
+pub enum ImproperStyle {
+    Harmonic { k: f64, chi0: f64 }, // K (chi - chi0)^2, chi0 in degrees
+}
+
+impl ImproperStyle {
+    fn from(yaml: &Yaml) -> ImproperStyle {
+        match yaml["style"].as_str() {
+            Some("harmonic") => ImproperStyle::Harmonic {
+                k: to_f64(&yaml["k"]),
+                chi0: to_f64(&yaml["chi0"]).to_radians(),
+            },
+            _ => panic!("Unknown improper style"),
+        }
+    }
+    // Energy and its derivative with respect to the angle between the planes
+    fn evaluate(&self, chi: f64) -> (f64, f64) {
+        match self {
+            ImproperStyle::Harmonic { k, chi0 } => {
+                // Difference wrapped into [-pi, pi)
+                let difference = (chi - chi0 + std::f64::consts::PI)
+                    .rem_euclid(2.0 * std::f64::consts::PI)
+                    - std::f64::consts::PI;
+                (k * difference.powi(2), 2.0 * k * difference)
+            }
+        }
+    }
+}
+
+// Coefficients of every bonded interaction type, read from the `bonded` section of the
+// potential with one map per kind of group, e.g.
+//   bonds: {oh: {style: harmonic, k: 22.9, r0: 1.0}}
+pub struct BondedModel {
+    bonds: HashMap<String, BondStyle>,
+    angles: HashMap<String, AngleStyle>,
+    dihedrals: HashMap<String, DihedralStyle>,
+    impropers: HashMap<String, ImproperStyle>,
+}
+
+fn read_styles<T>(yaml: &Yaml, parse: &dyn Fn(&Yaml) -> T) -> HashMap<String, T> {
+    match yaml {
+        Yaml::BadValue => HashMap::new(),
+        Yaml::Hash(types) => types
+            .iter()
+            .map(|(kind, coefficients)| {
+                let kind = match kind {
+                    Yaml::String(x) => x.clone(),
+                    Yaml::Integer(x) => x.to_string(),
+                    _ => panic!("Bonded interaction types must be names or integers"),
+                };
+                (kind, parse(coefficients))
+            })
+            .collect(),
+        _ => panic!("Bonded coefficients must be given by type"),
+    }
+}
+
+fn check_types<const N: usize, T>(
+    groups: &[BondedGroup<N>],
+    styles: &HashMap<String, T>,
+    name: &str,
+) {
+    for group in groups {
+        if !styles.contains_key(&group.kind) {
+            panic!("No coefficients defined for {} type {}", name, group.kind);
+        }
+    }
+}
+
+impl BondedModel {
+    pub fn from(definition: &Yaml) -> BondedModel {
+        BondedModel {
+            bonds: read_styles(&definition["bonds"], &BondStyle::from),
+            angles: read_styles(&definition["angles"], &AngleStyle::from),
+            dihedrals: read_styles(&definition["dihedrals"], &DihedralStyle::from),
+            impropers: read_styles(&definition["impropers"], &ImproperStyle::from),
+        }
+    }
+    pub fn check_topology(&self, topology: &Topology) {
+        check_types(&topology.bonds, &self.bonds, "bond");
+        check_types(&topology.angles, &self.angles, "angle");
+        check_types(&topology.dihedrals, &self.dihedrals, "dihedral");
+        check_types(&topology.impropers, &self.impropers, "improper");
+    }
+    // Adds the bonded terms to the atoms, returning the energies of bonds, angles,
+    // dihedrals and impropers
+    pub fn evaluate(&self, system: &mut SystemDefinition) -> [f64; 4] {
+        let (bonds_energy, bonds) =
+            accumulate_groups(system, &system.topology.bonds, &self.bonds, &|style, b| {
+                let r = b[0].norm();
+                let (energy, derivative) = style.evaluate(r);
+                let force = -derivative * b[0] / r;
+                (energy, [-force, force])
+            });
+        let (angles_energy, angles) = accumulate_groups(
+            system,
+            &system.topology.angles,
+            &self.angles,
+            &|style, b| {
+                let (u, v) = (-b[0], b[1]);
+                let (u_norm, v_norm) = (u.norm(), v.norm());
+                let cosine = (u.dot(&v) / (u_norm * v_norm)).clamp(-1.0, 1.0);
+                let (energy, derivative) = style.evaluate(cosine);
+                let first = -derivative * (v / (u_norm * v_norm) - cosine * u / u_norm.powi(2));
+                let last = -derivative * (u / (u_norm * v_norm) - cosine * v / v_norm.powi(2));
+                (energy, [first, -first - last, last])
+            },
+        );
+        let (dihedrals_energy, dihedrals) = accumulate_groups(
+            system,
+            &system.topology.dihedrals,
+            &self.dihedrals,
+            &|style, b| {
+                let (phi, gradients) = dihedral_angle(b);
+                let (energy, derivative) = style.evaluate(phi);
+                (energy, gradients.map(|x| -derivative * x))
+            },
+        );
+        let (impropers_energy, impropers) = accumulate_groups(
+            system,
+            &system.topology.impropers,
+            &self.impropers,
+            &|style, b| {
+                let (chi, gradients) = dihedral_angle(b);
+                let (energy, derivative) = style.evaluate(chi);
+                (energy, gradients.map(|x| -derivative * x))
+            },
+        );
+        let contributions = bonds.merge(angles).merge(dihedrals).merge(impropers);
+        system
+            .atoms
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(index, atom)| {
+                atom.current.potential_energy += contributions.potential_energies[index];
+                atom.current.force += contributions.forces[index];
+                atom.current.virial += contributions.virials[index];
+            });
+        [
+            bonds_energy,
+            angles_energy,
+            dihedrals_energy,
+            impropers_energy,
+        ]
+    }
+}
+
+// Energy and forces of a group of N atoms, from the N - 1 minimum image vectors joining each
+// atom to the next one
+type GroupInteraction<'a, T, const N: usize> =
+    dyn Fn(&T, &[Vector3<f64>]) -> (f64, [Vector3<f64>; N]) + Sync + 'a;
+
+// Sums the interactions of the groups over threads, sharing each group energy and virial
+// evenly among its atoms. Returns the total energy along with the per-atom contributions.
+fn accumulate_groups<const N: usize, T: Sync>(
+    system: &SystemDefinition,
+    groups: &[BondedGroup<N>],
+    styles: &HashMap<String, T>,
+    interaction: &GroupInteraction<T, N>,
+) -> (f64, PairContributions) {
+    let atoms_count = system.atoms.len();
+    let share = 1.0 / N as f64;
+    groups
+        .par_iter()
+        .fold(
+            || (0.0, PairContributions::zeros(atoms_count)),
+            |(total, mut contributions), group| {
+                let positions = group.atoms.map(|i| system.atoms[i].current.position);
+                let vectors = (0..N - 1)
+                    .map(|k| {
+                        system
+                            .simulation_box
+                            .minimum_image(positions[k + 1] - positions[k])
+                    })
+                    .collect::<Vec<Vector3<f64>>>();
+                let (energy, forces) = interaction(&styles[&group.kind], &vectors);
+                // W = sum_k r_k (x) f_k, with positions taken relative to the first atom
+                let mut relative_position = Vector3::zeros();
+                let mut virial = Matrix3::zeros();
+                for k in 0..N {
+                    if k > 0 {
+                        relative_position += vectors[k - 1];
+                    }
+                    virial += relative_position * forces[k].transpose();
+                }
+                for (atom, force) in group.atoms.iter().zip(forces) {
+                    contributions.add(*atom, share * energy, force, share * virial);
+                }
+                (total + energy, contributions)
+            },
+        )
+        .reduce(
+            || (0.0, PairContributions::zeros(atoms_count)),
+            |(first_total, first), (second_total, second)| {
+                (first_total + second_total, first.merge(second))
+            },
+        )
+}
+
+// Dihedral angle of four atoms (zero when cis, pi when trans) and its gradient with respect
+// to their positions, from the vectors b1, b2, b3 joining them
+fn dihedral_angle(b: &[Vector3<f64>]) -> (f64, [Vector3<f64>; 4]) {
+    let (b1, b2, b3) = (b[0], b[1], b[2]);
+    let m = b1.cross(&b2);
+    let n = b2.cross(&b3);
+    let b2_norm = b2.norm();
+    let phi = (b2_norm * b1.dot(&n)).atan2(m.dot(&n));
+    let first = -b2_norm / m.norm_squared() * m;
+    let last = b2_norm / n.norm_squared() * n;
+    let (p, q) = (b1.dot(&b2) / b2_norm.powi(2), b3.dot(&b2) / b2_norm.powi(2));
+    let second = -(1.0 + p) * first + q * last;
+    let third = p * first - (1.0 + q) * last;
+    (phi, [first, second, third, last])
+}
+
+impl std::fmt::Display for BondedModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Bonded terms with {} bond, {} angle, {} dihedral and {} improper types",
+            self.bonds.len(),
+            self.angles.len(),
+            self.dihedrals.len(),
+            self.impropers.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use yaml_rust::YamlLoader;
+
+    use super::*;
+    use crate::system::tests::molecules;
+    use crate::system::topology::tests::{butane as butane_topology, groups};
+
+    // Carbon chain with a hydrogen on the first carbon, in a gauche conformation
+    const BUTANE_POSITIONS: [[f64; 3]; 5] = [
+        [5.0, 5.0, 5.0],
+        [6.53, 5.0, 5.0],
+        [7.05, 6.44, 5.0],
+        [8.1, 6.7, 6.2],
+        [4.6, 4.4, 5.85],
+    ];
+
+    fn butane(topology: Topology) -> SystemDefinition {
+        let atoms = BUTANE_POSITIONS
+            .iter()
+            .enumerate()
+            .map(|(index, position)| (["C", "H"][index / 4], Vector3::from(*position)))
+            .collect::<Vec<(&str, Vector3<f64>)>>();
+        molecules(20.0, [false; 3], &atoms, topology)
+    }
+
+    fn model(definition: &str) -> BondedModel {
+        BondedModel::from(&YamlLoader::load_from_str(definition).unwrap()[0])
+    }
+
+    const HARMONIC_MODEL: &str = "
+        bonds:
+          cc: {style: harmonic, k: 9.3, r0: 1.5}
+          ch: {style: harmonic, k: 14.7, r0: 1.1}
+        angles:
+          ccc: {style: harmonic, k: 2.7, theta0: 112.7}
+          hcc: {style: harmonic, k: 1.6, theta0: 110.7}
+        dihedrals:
+          cccc: {style: opls, k: [0.075, -0.01, 0.014, 0.02]}
+          hccc: {style: opls, k: [0.0, 0.0, 0.013, 0.0]}
+        impropers:
+          x: {style: harmonic, k: 0.5, chi0: 30.0}
+    ";
+
+    const ANHARMONIC_MODEL: &str = "
+        bonds:
+          cc: {style: morse, depth: 3.6, alpha: 1.9, r0: 1.5}
+          ch: {style: morse, depth: 4.3, alpha: 1.8, r0: 1.1}
+        angles:
+          ccc: {style: cosine, k: 1.2}
+          hcc: {style: cosine, k: 0.8}
+        dihedrals:
+          cccc: {style: charmm, k: 0.1, n: 3, d: 0.0}
+          hccc: {style: charmm, k: 0.05, n: 2, d: 180.0}
+        impropers:
+          x: {style: harmonic, k: 0.5, chi0: -10.0}
+    ";
+
+    // Total energy and forces, starting from zero
+    fn evaluate(model: &BondedModel, system: &mut SystemDefinition) -> (f64, Vec<Vector3<f64>>) {
+        system.atoms.iter_mut().for_each(|atom| {
+            atom.current.potential_energy = 0.0;
+            atom.current.force = Vector3::zeros();
+            atom.current.virial = Matrix3::zeros();
+        });
+        let energy = model.evaluate(system).iter().sum();
+        let forces = system.atoms.iter().map(|atom| atom.current.force).collect();
+        (energy, forces)
+    }
+
+    fn check_forces(definition: &str) {
+        let model = model(definition);
+        let mut system = butane(butane_topology());
+        let (_, forces) = evaluate(&model, &mut system);
+        let step = 1e-6;
+        for (index, force) in forces.iter().enumerate() {
+            for i in 0..3 {
+                let mut energies = [0.0; 2];
+                for (k, sign) in [1.0, -1.0].iter().enumerate() {
+                    system.atoms[index].current.position[i] += sign * step;
+                    energies[k] = evaluate(&model, &mut system).0;
+                    system.atoms[index].current.position[i] -= sign * step;
+                }
+                let numerical_force = -(energies[0] - energies[1]) / (2.0 * step);
+                assert!(
+                    (force[i] - numerical_force).abs() < 1e-6,
+                    "force {} on atom {} along {} instead of {}",
+                    force[i],
+                    index,
+                    i,
+                    numerical_force
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn harmonic_forces_match_energy_gradient() {
+        check_forces(HARMONIC_MODEL);
+    }
+
+    #[test]
+    fn anharmonic_forces_match_energy_gradient() {
+        check_forces(ANHARMONIC_MODEL);
+    }
+
+    #[test]
+    fn dihedral_gradient_matches_finite_differences() {
+        let positions = BUTANE_POSITIONS[..4]
+            .iter()
+            .map(|x| Vector3::from(*x))
+            .collect::<Vec<Vector3<f64>>>();
+        let angle = |positions: &[Vector3<f64>]| {
+            let b = (0..3)
+                .map(|k| positions[k + 1] - positions[k])
+                .collect::<Vec<Vector3<f64>>>();
+            dihedral_angle(&b)
+        };
+        let (_, gradients) = angle(&positions);
+        let step = 1e-6;
+        for (index, gradient) in gradients.iter().enumerate() {
+            for i in 0..3 {
+                let mut forward = positions.clone();
+                let mut backward = positions.clone();
+                forward[index][i] += step;
+                backward[index][i] -= step;
+                let numerical_gradient = (angle(&forward).0 - angle(&backward).0) / (2.0 * step);
+                assert!((gradient[i] - numerical_gradient).abs() < 1e-7);
+            }
+        }
+    }
+
+    // Each group alone exerts no net force or torque on the molecule
+    #[test]
+    fn groups_exert_no_net_force_or_torque() {
+        let model = model(HARMONIC_MODEL);
+        let mut topologies = [
+            Topology::new(),
+            Topology::new(),
+            Topology::new(),
+            Topology::new(),
+        ];
+        topologies[0].bonds = groups("cc", &[[1, 2]]);
+        topologies[1].angles = groups("ccc", &[[1, 2, 3]]);
+        topologies[2].dihedrals = groups("cccc", &[[0, 1, 2, 3]]);
+        topologies[3].impropers = groups("x", &[[1, 0, 2, 4]]);
+        for (group, topology) in ["bond", "angle", "dihedral", "improper"]
+            .iter()
+            .zip(topologies)
+        {
+            let mut system = butane(topology);
+            let (energy, forces) = evaluate(&model, &mut system);
+            assert!(energy.abs() > 1e-6);
+            let net_force = forces.iter().sum::<Vector3<f64>>();
+            let net_torque = system
+                .atoms
+                .iter()
+                .zip(forces.iter())
+                .map(|(atom, force)| atom.current.position.cross(force))
+                .sum::<Vector3<f64>>();
+            assert!(
+                net_force.norm() < 1e-10,
+                "net force {} for {}",
+                net_force,
+                group
+            );
+            assert!(
+                net_torque.norm() < 1e-10,
+                "net torque {} for {}",
+                net_torque,
+                group
+            );
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use crate::io::input::to_f64;
+use crate::statics::models::pair_table::PairTable;
+use crate::statics::models::truncation::{exponential_tail, parse_tail_correction, Truncation};
+use crate::statics::models::CalculatePotential;
+use yaml_rust::Yaml;
+
+// V(r) = A exp((sigma - r) / rho) - C / r^6 - D / r^8
+// Buckingham is the special case sigma = D = 0
+#[derive(Clone)]
+pub struct BuckinghamPair {
+    a: f64,
+    rho: f64,
+    sigma: f64,
+    c: f64,
+    d: f64,
+    cutoff: f64,
+}
+
+impl BuckinghamPair {
+    fn from(
+        definition: &Yaml,
+        form: &BuckinghamForm,
+        default_cutoff: Option<f64>,
+    ) -> BuckinghamPair {
+        let (sigma, d) = match form {
+            BuckinghamForm::Buckingham => (0.0, 0.0),
+            BuckinghamForm::BornMayerHuggins => {
+                (to_f64(&definition["sigma"]), to_f64(&definition["d"]))
+            }
+        };
+        BuckinghamPair {
+            a: to_f64(&definition["a"]),
+            rho: to_f64(&definition["rho"]),
+            sigma,
+            c: to_f64(&definition["c"]),
+            d,
+            cutoff: match &definition["cutoff"] {
+                Yaml::BadValue => {
+                    default_cutoff.unwrap_or_else(|| panic!("{} potential requires a cutoff", form))
+                }
+                cutoff => to_f64(cutoff),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum BuckinghamForm {
+    Buckingham,
+    BornMayerHuggins,
+}
+
+impl std::fmt::Display for BuckinghamForm {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BuckinghamForm::Buckingham => write!(f, "Buckingham"),
+            BuckinghamForm::BornMayerHuggins => write!(f, "Born-Mayer-Huggins"),
+        }
+    }
+}
+
+// There is no common mixing rule for these potentials, unlike pairs must be given explicitly
+pub struct BuckinghamModel {
+    pub pairs: PairTable<BuckinghamPair>,
+    pub truncation: Truncation,
+    pub tail_correction: bool,
+    form: BuckinghamForm,
+}
+
+impl BuckinghamModel {
+    pub fn initialize(definition: &Yaml, form: BuckinghamForm) -> BuckinghamModel {
+        let default_cutoff = match &definition["cutoff"] {
+            Yaml::BadValue => None,
+            cutoff => Some(to_f64(cutoff)),
+        };
+        let model = BuckinghamModel {
+            pairs: PairTable::from(
+                definition,
+                &|yaml| BuckinghamPair::from(yaml, &form, default_cutoff),
+                None,
+            ),
+            truncation: Truncation::from(definition),
+            tail_correction: parse_tail_correction(definition),
+            form,
+        };
+        model.pairs.values().for_each(|pair| {
+            if pair.rho <= 0.0 {
+                panic!("{} rho must be positive", model.form);
+            }
+            model.truncation.check_cutoff(pair.cutoff)
+        });
+        model
+    }
+    pub fn max_cutoff(&self) -> f64 {
+        self.pairs.values().map(|x| x.cutoff).fold(0.0, f64::max)
+    }
+}
+
+impl std::fmt::Display for BuckinghamModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} with {}", self.form, self.truncation)
+    }
+}
+
+impl CalculatePotential for BuckinghamPair {
+    fn calculate_potential(&self, distance: f64) -> f64 {
+        match distance > 0.0 {
+            true => {
+                self.a * ((self.sigma - distance) / self.rho).exp()
+                    - self.c / distance.powi(6)
+                    - self.d / distance.powi(8)
+            }
+            // The dispersion terms diverge at r = 0
+            false => f64::NEG_INFINITY,
+        }
+    }
+    fn calculate_force(&self, distance: f64) -> f64 {
+        match distance > 0.0 {
+            // F = -dV/dr = A / rho exp((sigma - r) / rho) - 6 C / r^7 - 8 D / r^9
+            true => {
+                self.a / self.rho * ((self.sigma - distance) / self.rho).exp()
+                    - 6.0 * self.c / distance.powi(7)
+                    - 8.0 * self.d / distance.powi(9)
+            }
+            false => f64::NEG_INFINITY,
+        }
+    }
+    fn cutoff(&self) -> f64 {
+        self.cutoff
+    }
+    fn tail_integrals(&self) -> Option<(f64, f64)> {
+        let rc = self.cutoff;
+        let exponential = self.a * ((self.sigma - rc) / self.rho).exp();
+        let decay = 1.0 / self.rho;
+        Some((
+            exponential * exponential_tail(2, decay, rc)
+                - self.c / (3.0 * rc.powi(3))
+                - self.d / (5.0 * rc.powi(5)),
+            -exponential * decay * exponential_tail(3, decay, rc)
+                + 2.0 * self.c / rc.powi(3)
+                + 8.0 * self.d / (5.0 * rc.powi(5)),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use yaml_rust::YamlLoader;
+
+    use super::*;
+    use crate::statics::models::truncation::tests::{check_forces, truncation_settings};
+
+    fn check_model(form: BuckinghamForm, parameters: &str) {
+        for truncation in truncation_settings(6.5) {
+            let definition = YamlLoader::load_from_str(&format!(
+                "{{cutoff: 8.0, {}, parameters: {}}}",
+                truncation, parameters
+            ))
+            .unwrap();
+            let mut model = BuckinghamModel::initialize(&definition[0], form);
+            model
+                .pairs
+                .index_species(&["Na".to_string(), "Cl".to_string()]);
+            check_forces(
+                &model.truncation,
+                model.pairs.get_indexed(0, 1),
+                &[1.6, 2.2, 3.0, 4.5, 6.4, 6.6, 7.5, 7.99, 7.9999],
+            );
+        }
+    }
+
+    #[test]
+    fn buckingham_forces_match_energy_derivative() {
+        check_model(
+            BuckinghamForm::Buckingham,
+            "{a: 1388.77, rho: 0.3623, c: 175.0}",
+        );
+    }
+
+    #[test]
+    fn born_mayer_huggins_forces_match_energy_derivative() {
+        check_model(
+            BuckinghamForm::BornMayerHuggins,
+            "{a: 0.2637, rho: 0.317, sigma: 2.755, c: 72.4, d: 145.4}",
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use yaml_rust::Yaml;
+
+use crate::dynamics::neighbors::NeighborsList;
+use crate::statics::models::bonded::SpecialFactors;
+use crate::statics::models::cutoff_coulomb::CutoffCoulomb;
+use crate::statics::models::ewald::Ewald;
+use crate::statics::models::pme::ParticleMesh;
+use crate::system::SystemDefinition;
+
+// Electrostatic interactions between the atom charges
+pub enum Electrostatics {
+    Ewald(Ewald),
+    ParticleMesh(ParticleMesh),
+    Cutoff(CutoffCoulomb),
+}
+
+impl Electrostatics {
+    pub fn from(definition: &Yaml) -> Electrostatics {
+        match definition["method"].as_str() {
+            Some("ewald") => Electrostatics::Ewald(Ewald::from(definition)),
+            Some("pme") => Electrostatics::ParticleMesh(ParticleMesh::from(definition)),
+            Some(method @ ("cutoff" | "dsf" | "wolf" | "reaction_field")) => {
+                Electrostatics::Cutoff(CutoffCoulomb::from(definition, method))
+            }
+            Some(_) => panic!("Unknown electrostatics method"),
+            None => panic!("Coulomb interactions require a method"),
+        }
+    }
+    // Distance up to which charges interact through the neighbors list
+    pub fn cutoff(&self) -> f64 {
+        match self {
+            Electrostatics::Ewald(ewald) => ewald.cutoff,
+            Electrostatics::ParticleMesh(mesh) => mesh.cutoff,
+            Electrostatics::Cutoff(coulomb) => coulomb.cutoff,
+        }
+    }
+    pub fn evaluate(
+        &self,
+        system: &mut SystemDefinition,
+        neighbors_list: &NeighborsList,
+        special_factors: &SpecialFactors,
+    ) {
+        match self {
+            Electrostatics::Ewald(ewald) => ewald.evaluate(system, neighbors_list, special_factors),
+            Electrostatics::ParticleMesh(mesh) => {
+                mesh.evaluate(system, neighbors_list, special_factors)
+            }
+            Electrostatics::Cutoff(coulomb) => {
+                coulomb.evaluate(system, neighbors_list, special_factors)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Electrostatics {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Electrostatics::Ewald(x) => write!(f, "{}", x),
+            Electrostatics::ParticleMesh(x) => write!(f, "{}", x),
+            Electrostatics::Cutoff(x) => write!(f, "{}", x),
+        }
+    }
+}
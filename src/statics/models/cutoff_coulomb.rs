@@ -0,0 +1,284 @@
+use std::f64::consts::PI;
+
+use rayon::prelude::*;
+use yaml_rust::Yaml;
+
+use crate::dynamics::neighbors::NeighborsList;
+use crate::io::input::to_f64;
+use crate::statics::models::accumulate_pairs;
+use crate::statics::models::bonded::SpecialFactors;
+use crate::system::SystemDefinition;
+
+// How the Coulomb interaction is brought to the cutoff
+pub enum CutoffScheme {
+    // Bare 1/r, dropped beyond the cutoff
+    Plain,
+    // Damped shifted force of Fennell and Gezelter, Wolf's damping with both the energy and the
+    // force going to zero at the cutoff. No damping leaves the shifted-force Coulomb interaction.
+    DampedShiftedForce { alpha: f64 },
+    // Charges beyond the cutoff seen as a continuum of the external dielectric constant
+    // (infinite for conducting surroundings)
+    ReactionField { external_dielectric: f64 },
+}
+
+impl std::fmt::Display for CutoffScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CutoffScheme::Plain => write!(f, "Cutoff Coulomb"),
+            CutoffScheme::DampedShiftedForce { alpha } => {
+                write!(f, "Damped shifted force Coulomb with alpha {}", alpha)
+            }
+            CutoffScheme::ReactionField {
+                external_dielectric,
+            } => write!(
+                f,
+                "Reaction field Coulomb with external dielectric constant {}",
+                external_dielectric
+            ),
+        }
+    }
+}
+
+// Pairwise electrostatics over the neighbors list, which needs no periodicity
+pub struct CutoffCoulomb {
+    pub cutoff: f64,
+    dielectric: f64,
+    scheme: CutoffScheme,
+}
+
+impl CutoffCoulomb {
+    pub fn from(definition: &Yaml, scheme: &str) -> CutoffCoulomb {
+        let scheme = match scheme {
+            "cutoff" => CutoffScheme::Plain,
+            "dsf" | "wolf" => CutoffScheme::DampedShiftedForce {
+                alpha: match &definition["alpha"] {
+                    Yaml::BadValue => panic!("Damped shifted force Coulomb requires an alpha"),
+                    alpha => to_f64(alpha),
+                },
+            },
+            "reaction_field" => CutoffScheme::ReactionField {
+                external_dielectric: match &definition["external_dielectric"] {
+                    Yaml::BadValue => f64::INFINITY,
+                    dielectric => to_f64(dielectric),
+                },
+            },
+            _ => panic!("Unknown electrostatics method"),
+        };
+        CutoffCoulomb {
+            cutoff: match &definition["cutoff"] {
+                Yaml::BadValue => panic!("Cutoff Coulomb interactions require a cutoff"),
+                cutoff => to_f64(cutoff),
+            },
+            dielectric: match &definition["dielectric"] {
+                Yaml::BadValue => 1.0,
+                dielectric => to_f64(dielectric),
+            },
+            scheme,
+        }
+    }
+    pub fn evaluate(
+        &self,
+        system: &mut SystemDefinition,
+        neighbors_list: &NeighborsList,
+        special_factors: &SpecialFactors,
+    ) {
+        let charges = system
+            .atoms
+            .iter()
+            .map(|atom| atom.charge)
+            .collect::<Vec<f64>>();
+        let prefactor = system.units.coulomb_constant() / self.dielectric;
+        let cutoff = self.cutoff;
+        match self.scheme {
+            CutoffScheme::Plain => accumulate_pairs(
+                &mut system.atoms,
+                neighbors_list,
+                &|i, j, r| {
+                    if r >= cutoff {
+                        return (0.0, 0.0);
+                    }
+                    let charges_product = prefactor * charges[i] * charges[j];
+                    (charges_product / r, charges_product / r.powi(2))
+                },
+                special_factors,
+            ),
+            CutoffScheme::DampedShiftedForce { alpha } => {
+                // erfc(alpha r) / r and minus its derivative
+                let screened = |r: f64| {
+                    let value = libm::erfc(alpha * r) / r;
+                    let force =
+                        (value + 2.0 * alpha / PI.sqrt() * (-(alpha * r).powi(2)).exp()) / r;
+                    (value, force)
+                };
+                let (shift, force_shift) = screened(cutoff);
+                accumulate_pairs(
+                    &mut system.atoms,
+                    neighbors_list,
+                    &|i, j, r| {
+                        if r >= cutoff {
+                            return (0.0, 0.0);
+                        }
+                        let charges_product = prefactor * charges[i] * charges[j];
+                        let (value, force) = screened(r);
+                        (
+                            charges_product * (value - shift + force_shift * (r - cutoff)),
+                            charges_product * (force - force_shift),
+                        )
+                    },
+                    special_factors,
+                );
+                // Interaction of each charge with its own image in the cutoff sphere
+                let self_energy = prefactor * (0.5 * shift + alpha / PI.sqrt());
+                system.atoms.par_iter_mut().for_each(|atom| {
+                    atom.current.potential_energy -= self_energy * atom.charge.powi(2);
+                });
+            }
+            CutoffScheme::ReactionField {
+                external_dielectric,
+            } => {
+                let reaction = match external_dielectric.is_infinite() {
+                    true => 0.5 / cutoff.powi(3),
+                    false => {
+                        (external_dielectric - self.dielectric)
+                            / ((2.0 * external_dielectric + self.dielectric) * cutoff.powi(3))
+                    }
+                };
+                // Shift bringing the energy to zero at the cutoff
+                let shift = 1.0 / cutoff + reaction * cutoff.powi(2);
+                accumulate_pairs(
+                    &mut system.atoms,
+                    neighbors_list,
+                    &|i, j, r| {
+                        if r >= cutoff {
+                            return (0.0, 0.0);
+                        }
+                        let charges_product = prefactor * charges[i] * charges[j];
+                        (
+                            charges_product * (1.0 / r + reaction * r.powi(2) - shift),
+                            charges_product * (1.0 / r.powi(2) - 2.0 * reaction * r),
+                        )
+                    },
+                    special_factors,
+                );
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for CutoffCoulomb {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} with cutoff {} and dielectric constant {}",
+            self.scheme, self.cutoff, self.dielectric
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use yaml_rust::YamlLoader;
+
+    use super::*;
+    use crate::statics::models::bonded::UNSCALED;
+    use crate::statics::models::tests::{check_numerical_forces, evaluate, system};
+
+    const CUTOFF: f64 = 6.0;
+
+    fn coulomb(method: &str) -> CutoffCoulomb {
+        let definition = format!(
+            "{{cutoff: {}, alpha: 0.25, external_dielectric: 78.0, dielectric: 2.0}}",
+            CUTOFF
+        );
+        CutoffCoulomb::from(&YamlLoader::load_from_str(&definition).unwrap()[0], method)
+    }
+
+    // Energy and forces of charges 1 and -0.5 on the x axis, over a full neighbors list
+    fn pair(coulomb: &CutoffCoulomb, distance: f64) -> (f64, Vec<Vector3<f64>>) {
+        let mut system = system(&format!(
+            "
+            cell: [[20.0, 0, 0], [0, 20.0, 0], [0, 0, 20.0]]
+            units: atomic
+            atoms:
+              - {{name: Na, charge: 1.0, position: [0.2, 0.5, 0.5]}}
+              - {{name: Cl, charge: -0.5, position: [{}, 0.5, 0.5]}}
+            ",
+            0.2 + distance / 20.0
+        ));
+        let (energy, forces, _) = evaluate(&mut system, CUTOFF + 1.0, false, &|system, list| {
+            coulomb.evaluate(system, list, &UNSCALED)
+        });
+        (energy, forces)
+    }
+
+    #[test]
+    fn shifted_interactions_vanish_at_cutoff() {
+        let conducting = CutoffCoulomb {
+            scheme: CutoffScheme::ReactionField {
+                external_dielectric: f64::INFINITY,
+            },
+            ..coulomb("reaction_field")
+        };
+        // The force of a finite external dielectric keeps a jump at the cutoff
+        for (model, vanishing_force) in [
+            (coulomb("dsf"), true),
+            (conducting, true),
+            (coulomb("reaction_field"), false),
+        ] {
+            // Only the self terms remain beyond the cutoff
+            let (inside_energy, inside_forces) = pair(&model, CUTOFF - 1e-6);
+            let (outside_energy, outside_forces) = pair(&model, CUTOFF + 1e-6);
+            assert!((inside_energy - outside_energy).abs() < 1e-6);
+            assert!(outside_forces.iter().all(|force| force.norm() == 0.0));
+            if vanishing_force {
+                assert!(inside_forces.iter().all(|force| force.norm() < 1e-6));
+            }
+        }
+    }
+
+    #[test]
+    fn damped_shifted_force_includes_self_energy() {
+        let coulomb = coulomb("dsf");
+        let mut system = system(
+            "
+            cell: [[20.0, 0, 0], [0, 20.0, 0], [0, 0, 20.0]]
+            units: atomic
+            atoms:
+              - {name: Na, charge: 1.5, position: [0.5, 0.5, 0.5]}
+            ",
+        );
+        let (energy, _, _) = evaluate(&mut system, CUTOFF, false, &|system, list| {
+            coulomb.evaluate(system, list, &UNSCALED)
+        });
+        let alpha: f64 = 0.25;
+        let expected = -system.units.coulomb_constant() / 2.0
+            * 1.5_f64.powi(2)
+            * (0.5 * libm::erfc(alpha * CUTOFF) / CUTOFF + alpha / PI.sqrt());
+        assert!((energy - expected).abs() < 1e-12 * expected.abs());
+    }
+
+    #[test]
+    fn forces_match_energy_derivatives() {
+        let mut system = system(
+            "
+            cell: [[12.0, 0, 0], [0, 12.0, 0], [0, 0, 12.0]]
+            units: atomic
+            atoms:
+              - {name: Na, charge: 1.0, position: [0.30, 0.35, 0.40]}
+              - {name: Cl, charge: -0.8, position: [0.52, 0.41, 0.33]}
+              - {name: Na, charge: 0.6, position: [0.45, 0.62, 0.55]}
+              - {name: Cl, charge: -1.2, position: [0.68, 0.58, 0.70]}
+              - {name: Na, charge: 0.3, position: [0.36, 0.66, 0.21]}
+            ",
+        );
+        for method in ["cutoff", "dsf", "reaction_field"] {
+            let coulomb = coulomb(method);
+            for half in [true, false] {
+                check_numerical_forces(&mut system, CUTOFF, half, &|system, list| {
+                    coulomb.evaluate(system, list, &UNSCALED)
+                });
+            }
+        }
+    }
+}
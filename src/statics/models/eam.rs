@@ -0,0 +1,573 @@
+use std::fs::read_to_string;
+
+use periodic_table_on_an_enum::Element;
+use rayon::prelude::*;
+use yaml_rust::Yaml;
+
+use crate::dynamics::neighbors::NeighborsList;
+use crate::statics::models::bonded::UNSCALED;
+use crate::statics::models::pair_table::SpeciesSelection;
+use crate::statics::models::{accumulate_pair_scalars, accumulate_pairs};
+use crate::system::atom::Atom;
+use crate::utils::spline::Spline;
+
+// Conversion of funcfl effective charges products Z_a Z_b into eV * Angstrom, as used by DYNAMO
+const HARTREE_BOHR: f64 = 27.2 * 0.529;
+
+pub enum EamFormat {
+    Funcfl,         // One file per element with effective charges for the pair term
+    Setfl,          // Single file for an alloy, with one density function per element
+    FinnisSinclair, // Setfl layout with one density function per pair of elements
+}
+
+impl EamFormat {
+    fn from(definition: &Yaml, filename: &str) -> EamFormat {
+        match definition["format"].as_str() {
+            Some("funcfl") => EamFormat::Funcfl,
+            Some("setfl") | Some("alloy") => EamFormat::Setfl,
+            Some("fs") | Some("finnis_sinclair") => EamFormat::FinnisSinclair,
+            Some(_) => panic!("Unknown EAM file format"),
+            None => match filename.rsplit('.').next() {
+                Some("eam") => EamFormat::Funcfl,
+                Some("fs") => EamFormat::FinnisSinclair,
+                _ => EamFormat::Setfl,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for EamFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EamFormat::Funcfl => write!(f, "funcfl"),
+            EamFormat::Setfl => write!(f, "setfl"),
+            EamFormat::FinnisSinclair => write!(f, "Finnis-Sinclair"),
+        }
+    }
+}
+
+// Pair term, stored as r * phi(r) like in the tabulated files
+enum PairFunction {
+    Tabulated(Spline),
+    EffectiveCharges(Spline, Spline),
+}
+
+impl PairFunction {
+    // phi(r) and its derivative
+    fn evaluate(&self, distance: f64) -> (f64, f64) {
+        let (scaled_potential, scaled_derivative) = match self {
+            PairFunction::Tabulated(x) => x.evaluate(distance),
+            PairFunction::EffectiveCharges(first, second) => {
+                let (first_charge, first_derivative) = first.evaluate(distance);
+                let (second_charge, second_derivative) = second.evaluate(distance);
+                (
+                    HARTREE_BOHR * first_charge * second_charge,
+                    HARTREE_BOHR
+                        * (first_derivative * second_charge + first_charge * second_derivative),
+                )
+            }
+        };
+        let potential = scaled_potential / distance;
+        (potential, (scaled_derivative - potential) / distance)
+    }
+}
+
+// Tokens of a tabulated file past its header lines
+struct TableReader {
+    tokens: Vec<String>,
+    position: usize,
+    filename: String,
+}
+
+impl TableReader {
+    fn new(filename: &str, lines: &[&str]) -> TableReader {
+        TableReader {
+            tokens: lines
+                .iter()
+                .flat_map(|line| line.split_whitespace())
+                .map(|x| x.to_string())
+                .collect(),
+            position: 0,
+            filename: filename.to_string(),
+        }
+    }
+    fn next(&mut self) -> &str {
+        match self.tokens.get(self.position) {
+            Some(token) => {
+                self.position += 1;
+                token
+            }
+            None => panic!("Unexpected end of EAM file {}", self.filename),
+        }
+    }
+    fn next_f64(&mut self) -> f64 {
+        let filename = self.filename.clone();
+        match self.next().parse::<f64>() {
+            Ok(x) => x,
+            Err(_) => panic!("Invalid number in EAM file {}", filename),
+        }
+    }
+    fn next_usize(&mut self) -> usize {
+        self.next_f64() as usize
+    }
+    fn next_spline(&mut self, points_count: usize, step: f64) -> Spline {
+        let values = (0..points_count).map(|_| self.next_f64()).collect();
+        Spline::uniform(0.0, step, values)
+    }
+}
+
+fn read_file(filename: &str) -> String {
+    match read_to_string(filename) {
+        Ok(x) => x,
+        Err(_) => panic!("Failed to read EAM file {}", filename),
+    }
+}
+
+pub struct EamModel {
+    elements: Vec<String>,
+    embedding: Vec<Spline>,        // F_a(rho)
+    densities: Vec<Vec<Spline>>,   // [a][b]: density at an atom of element a due to element b
+    pairs: Vec<Vec<PairFunction>>, // [a][b]: pair term between elements a and b
+    cutoff: f64,
+    format: EamFormat,
+    // Element of every species index, None for species outside of the selection
+    species_elements: Vec<Option<usize>>,
+}
+
+impl EamModel {
+    pub fn initialize(definition: &Yaml) -> EamModel {
+        let filenames = match (&definition["file"], &definition["files"]) {
+            (Yaml::String(x), Yaml::BadValue) => vec![x.clone()],
+            (Yaml::BadValue, Yaml::Array(x)) => x
+                .iter()
+                .map(|x| match x.as_str() {
+                    Some(x) => x.to_string(),
+                    None => panic!("EAM file names must be strings"),
+                })
+                .collect(),
+            _ => panic!("EAM potential requires either a file or a list of files"),
+        };
+        let format = EamFormat::from(definition, &filenames[0]);
+        let files = filenames
+            .into_iter()
+            .map(|filename| {
+                let contents = read_file(&filename);
+                (filename, contents)
+            })
+            .collect::<Vec<(String, String)>>();
+        let mut model = match format {
+            EamFormat::Funcfl => EamModel::from_funcfl(&files),
+            _ => match files.len() {
+                1 => EamModel::from_setfl(&files[0].0, &files[0].1, format),
+                _ => panic!("Only funcfl potentials can be combined from several files"),
+            },
+        };
+        // Species names may differ from the element names found in the files
+        if let Some(elements) = definition["elements"].as_vec() {
+            if elements.len() != model.elements.len() {
+                panic!(
+                    "EAM files define {} elements, {} names given",
+                    model.elements.len(),
+                    elements.len()
+                );
+            }
+            model.elements = elements
+                .iter()
+                .map(|x| match x.as_str() {
+                    Some(x) => x.to_string(),
+                    None => panic!("EAM element names must be strings"),
+                })
+                .collect();
+        }
+        model
+    }
+    // Header lines: comment, "Z mass lattice_constant lattice", "Nrho drho Nr dr cutoff",
+    // followed by F(rho), Z(r) and rho(r). Files are given as (filename, contents).
+    fn from_funcfl(files: &[(String, String)]) -> EamModel {
+        let mut elements = Vec::new();
+        let mut embedding = Vec::new();
+        let mut charges = Vec::new();
+        let mut densities = Vec::new();
+        let mut cutoff: f64 = 0.0;
+        for (filename, contents) in files {
+            let lines = contents.lines().collect::<Vec<&str>>();
+            if lines.len() < 3 {
+                panic!("Invalid funcfl file {}", filename);
+            }
+            let mut reader = TableReader::new(filename, &lines[1..]);
+            let atomic_number = reader.next_usize();
+            elements.push(match Element::from_atomic_number(atomic_number) {
+                Some(element) => element.get_symbol().to_string(),
+                None => panic!("Unknown atomic number {} in {}", atomic_number, filename),
+            });
+            for _ in 0..3 {
+                reader.next();
+            }
+            let density_points = reader.next_usize();
+            let density_step = reader.next_f64();
+            let distance_points = reader.next_usize();
+            let distance_step = reader.next_f64();
+            cutoff = cutoff.max(reader.next_f64());
+            embedding.push(reader.next_spline(density_points, density_step));
+            charges.push(reader.next_spline(distance_points, distance_step));
+            densities.push(reader.next_spline(distance_points, distance_step));
+        }
+        let elements_count = elements.len();
+        EamModel {
+            elements,
+            embedding,
+            // Each element carries its own density function, whoever receives it
+            densities: (0..elements_count).map(|_| densities.clone()).collect(),
+            pairs: (0..elements_count)
+                .map(|a| {
+                    (0..elements_count)
+                        .map(|b| {
+                            PairFunction::EffectiveCharges(charges[a].clone(), charges[b].clone())
+                        })
+                        .collect()
+                })
+                .collect(),
+            cutoff,
+            format: EamFormat::Funcfl,
+            species_elements: Vec::new(),
+        }
+    }
+    // Header lines: three comments, "N element_1 ... element_N", "Nrho drho Nr dr cutoff".
+    // Then for every element "Z mass lattice_constant lattice", F(rho) and either rho(r) or,
+    // for Finnis-Sinclair files, N density functions. The file ends with r * phi(r) for every
+    // pair (a, b) with b <= a.
+    fn from_setfl(filename: &str, contents: &str, format: EamFormat) -> EamModel {
+        let lines = contents.lines().collect::<Vec<&str>>();
+        if lines.len() < 5 {
+            panic!("Invalid setfl file {}", filename);
+        }
+        let mut reader = TableReader::new(filename, &lines[3..]);
+        let elements_count = reader.next_usize();
+        let elements = (0..elements_count)
+            .map(|_| reader.next().to_string())
+            .collect::<Vec<String>>();
+        let density_points = reader.next_usize();
+        let density_step = reader.next_f64();
+        let distance_points = reader.next_usize();
+        let distance_step = reader.next_f64();
+        let cutoff = reader.next_f64();
+        let finnis_sinclair = matches!(format, EamFormat::FinnisSinclair);
+
+        let mut embedding = Vec::new();
+        // Density functions as stored, indexed by the emitting element first
+        let mut emitted_densities: Vec<Vec<Spline>> = Vec::new();
+        for _ in 0..elements_count {
+            for _ in 0..4 {
+                reader.next();
+            }
+            embedding.push(reader.next_spline(density_points, density_step));
+            emitted_densities.push(match finnis_sinclair {
+                true => (0..elements_count)
+                    .map(|_| reader.next_spline(distance_points, distance_step))
+                    .collect(),
+                false => vec![reader.next_spline(distance_points, distance_step)],
+            });
+        }
+        let densities = (0..elements_count)
+            .map(|a| {
+                (0..elements_count)
+                    .map(|b| match finnis_sinclair {
+                        true => emitted_densities[b][a].clone(),
+                        false => emitted_densities[b][0].clone(),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // Only the lower triangle of the pair functions is stored, row by row
+        let lower_pairs: Vec<Vec<Spline>> = (0..elements_count)
+            .map(|a| {
+                (0..=a)
+                    .map(|_| reader.next_spline(distance_points, distance_step))
+                    .collect()
+            })
+            .collect();
+        EamModel {
+            elements,
+            embedding,
+            densities,
+            pairs: (0..elements_count)
+                .map(|a| {
+                    (0..elements_count)
+                        .map(|b| PairFunction::Tabulated(lower_pairs[a.max(b)][a.min(b)].clone()))
+                        .collect()
+                })
+                .collect(),
+            cutoff,
+            format,
+            species_elements: Vec::new(),
+        }
+    }
+    pub fn max_cutoff(&self) -> f64 {
+        self.cutoff
+    }
+    fn element_index(&self, species: &str) -> usize {
+        match self.elements.iter().position(|x| x == species) {
+            Some(index) => index,
+            None => panic!("No EAM functions defined for species {}", species),
+        }
+    }
+    // Maps every species index to its element, once the species of the system are known
+    pub fn index_species(&mut self, species: &[String], selection: &SpeciesSelection) {
+        self.species_elements = species
+            .iter()
+            .enumerate()
+            .map(|(index, name)| match selection.includes_species(index) {
+                true => Some(self.element_index(name)),
+                false => None,
+            })
+            .collect();
+    }
+    // E_i = F_a(sum_j rho_ab(r_ij)) + 1/2 sum_j phi_ab(r_ij), over the selected pairs only.
+    // Atoms of species outside of the selection are left out, neither embedded nor interacting.
+    pub fn evaluate(
+        &self,
+        atoms: &mut [Atom],
+        neighbors_list: &NeighborsList,
+        species: &[usize],
+        selection: &SpeciesSelection,
+    ) {
+        let element = |i: usize| self.species_elements[species[i]];
+        let pair_elements = |i: usize, j: usize, r: f64| {
+            if r >= self.cutoff || !selection.includes_pair(species[i], species[j]) {
+                return None;
+            }
+            element(i).zip(element(j))
+        };
+        // First pass: host electron density at every atom
+        let host_densities = accumulate_pair_scalars(
+            atoms.len(),
+            neighbors_list,
+            &|i, j, r| match pair_elements(i, j, r) {
+                Some((a, b)) => self.densities[a][b].evaluate(r).0,
+                None => 0.0,
+            },
+        );
+        let embedding = host_densities
+            .par_iter()
+            .enumerate()
+            .map(|(i, density)| match element(i) {
+                Some(element) => self.embedding[element].evaluate(*density),
+                None => (0.0, 0.0),
+            })
+            .collect::<Vec<(f64, f64)>>();
+        // Second pass: pair terms plus the embedding forces, which depend on both densities
+        accumulate_pairs(
+            atoms,
+            neighbors_list,
+            &|i, j, r| {
+                let (a, b) = match pair_elements(i, j, r) {
+                    Some(x) => x,
+                    None => return (0.0, 0.0),
+                };
+                let (pair_potential, pair_derivative) = self.pairs[a][b].evaluate(r);
+                let (_, received_derivative) = self.densities[a][b].evaluate(r);
+                let (_, emitted_derivative) = self.densities[b][a].evaluate(r);
+                let derivative = pair_derivative
+                    + embedding[i].1 * received_derivative
+                    + embedding[j].1 * emitted_derivative;
+                (pair_potential, -derivative)
+            },
+            &UNSCALED,
+        );
+        atoms
+            .par_iter_mut()
+            .zip(embedding.par_iter())
+            .for_each(|(atom, (embedding_energy, _))| {
+                atom.current.potential_energy += embedding_energy
+            });
+    }
+}
+
+impl std::fmt::Display for EamModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Embedded atom method ({}) for {}",
+            self.format,
+            self.elements.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::statics::models::tests::{check_numerical_forces, evaluate, system};
+    use crate::system::SystemDefinition;
+
+    const POINTS: usize = 501;
+    const DENSITY_STEP: f64 = 0.02;
+    const DISTANCE_STEP: f64 = 0.01;
+    const CUTOFF: f64 = 5.0;
+
+    fn embedding(a: usize) -> impl Fn(f64) -> f64 {
+        move |rho| (0.5 + 0.2 * a as f64) * rho * rho - (2.0 + a as f64) * rho
+    }
+
+    // Density at an atom of element a due to an atom of element b
+    fn density(a: usize, b: usize) -> impl Fn(f64) -> f64 {
+        move |r| (1.0 + a as f64 + 2.0 * b as f64) * ((CUTOFF - r) / CUTOFF).powi(3)
+    }
+
+    // r * phi(r) between elements a and b
+    fn scaled_pair(a: usize, b: usize) -> impl Fn(f64) -> f64 {
+        move |r| 0.1 * (1.0 + (a + b) as f64) * (CUTOFF - r).powi(3) * (3.0 - r)
+    }
+
+    // Values on the grid, five per line like in the distributed files
+    fn tabulate(function: impl Fn(f64) -> f64, step: f64) -> String {
+        let values = (0..POINTS)
+            .map(|i| format!("{:.12e}", function(step * i as f64)))
+            .collect::<Vec<String>>();
+        values.chunks(5).map(|x| x.join(" ") + "\n").collect()
+    }
+
+    fn funcfl_file(atomic_number: usize, charge: f64, element: usize) -> String {
+        format!(
+            "funcfl test\n{} 63.5 3.6 fcc\n{} {} {} {} {}\n{}{}{}",
+            atomic_number,
+            POINTS,
+            DENSITY_STEP,
+            POINTS,
+            DISTANCE_STEP,
+            CUTOFF,
+            tabulate(embedding(element), DENSITY_STEP),
+            tabulate(|r| charge * (CUTOFF - r).powi(2), DISTANCE_STEP),
+            tabulate(density(element, element), DISTANCE_STEP)
+        )
+    }
+
+    // Cu-Ni alloy, setfl files storing the density emitted by each element and Finnis-Sinclair
+    // ones the density each element emits towards every other one
+    fn alloy_file(finnis_sinclair: bool) -> String {
+        let mut contents = format!(
+            "alloy test\n\n\n2 Cu Ni\n{} {} {} {} {}\n",
+            POINTS, DENSITY_STEP, POINTS, DISTANCE_STEP, CUTOFF
+        );
+        for (b, atomic_number) in [29, 28].iter().enumerate() {
+            contents += &format!("{} 60.0 3.6 fcc\n", atomic_number);
+            contents += &tabulate(embedding(b), DENSITY_STEP);
+            match finnis_sinclair {
+                true => (0..2).for_each(|a| contents += &tabulate(density(a, b), DISTANCE_STEP)),
+                false => contents += &tabulate(density(b, b), DISTANCE_STEP),
+            }
+        }
+        for a in 0..2 {
+            for b in 0..=a {
+                contents += &tabulate(scaled_pair(a, b), DISTANCE_STEP);
+            }
+        }
+        contents
+    }
+
+    fn assert_close(found: f64, expected: f64) {
+        assert!(
+            (found - expected).abs() < 1e-9 * expected.abs().max(1.0),
+            "{} instead of {}",
+            found,
+            expected
+        );
+    }
+
+    #[test]
+    fn funcfl_files_combine_into_an_alloy() {
+        let model = EamModel::from_funcfl(&[
+            ("Cu_u3.eam".to_string(), funcfl_file(29, 1.5, 0)),
+            ("Ni_u3.eam".to_string(), funcfl_file(28, 2.0, 1)),
+        ]);
+        assert_eq!(model.elements, ["Cu", "Ni"]);
+        assert_eq!(model.max_cutoff(), CUTOFF);
+        let r = 2.5;
+        // Densities only depend on the emitting element
+        for a in 0..2 {
+            for b in 0..2 {
+                assert_close(model.densities[a][b].evaluate(r).0, density(b, b)(r));
+            }
+        }
+        assert_close(model.embedding[1].evaluate(1.2).0, embedding(1)(1.2));
+        let charges_product = 1.5 * 2.0 * (CUTOFF - r).powi(4);
+        assert_close(
+            model.pairs[0][1].evaluate(r).0,
+            HARTREE_BOHR * charges_product / r,
+        );
+    }
+
+    #[test]
+    fn setfl_and_finnis_sinclair_files_are_read() {
+        let r = 2.5;
+        for (format, finnis_sinclair) in
+            [(EamFormat::Setfl, false), (EamFormat::FinnisSinclair, true)]
+        {
+            let model =
+                EamModel::from_setfl("CuNi.eam.alloy", &alloy_file(finnis_sinclair), format);
+            assert_eq!(model.elements, ["Cu", "Ni"]);
+            for a in 0..2 {
+                assert_close(model.embedding[a].evaluate(1.2).0, embedding(a)(1.2));
+                for b in 0..2 {
+                    let expected_density = match finnis_sinclair {
+                        true => density(a, b)(r),
+                        false => density(b, b)(r),
+                    };
+                    assert_close(model.densities[a][b].evaluate(r).0, expected_density);
+                    // Only the lower triangle is stored, the upper one mirrors it
+                    let expected_pair = scaled_pair(a.max(b), a.min(b))(r) / r;
+                    assert_close(model.pairs[a][b].evaluate(r).0, expected_pair);
+                }
+            }
+        }
+    }
+
+    // Small Cu-Ni cluster, within the cutoff of one another for the most part
+    fn cluster() -> SystemDefinition {
+        system(
+            "
+            cell: [[14.0, 0, 0], [0, 14.0, 0], [0, 0, 14.0]]
+            periodicity: xyz
+            units: atomic
+            atoms:
+              - {name: Cu, position: [0.50, 0.50, 0.50]}
+              - {name: Ni, position: [0.68, 0.52, 0.49]}
+              - {name: Cu, position: [0.55, 0.67, 0.52]}
+              - {name: Ni, position: [0.51, 0.55, 0.68]}
+              - {name: Ni, position: [0.36, 0.44, 0.55]}
+              - {name: Cu, position: [0.70, 0.70, 0.66]}
+            ",
+        )
+    }
+
+    #[test]
+    fn alloy_forces_match_energy_derivatives() {
+        let mut model =
+            EamModel::from_setfl("CuNi.eam.fs", &alloy_file(true), EamFormat::FinnisSinclair);
+        assert!(model.densities[0][1].evaluate(2.5).0 != model.densities[1][0].evaluate(2.5).0);
+        let selection = SpeciesSelection::from(&Yaml::BadValue);
+        // Species indexed in the opposite order of the elements in the file
+        let names = ["Ni".to_string(), "Cu".to_string()];
+        model.index_species(&names, &selection);
+        let mut system = cluster();
+        let species = system
+            .atoms
+            .iter()
+            .map(|atom| names.iter().position(|x| *x == atom.name).unwrap())
+            .collect::<Vec<usize>>();
+        let evaluate_model = |system: &mut SystemDefinition, neighbors_list: &NeighborsList| {
+            model.evaluate(&mut system.atoms, neighbors_list, &species, &selection)
+        };
+        // Densities and embedding forces from half lists reach both atoms of every pair
+        let (energy, forces, _) = evaluate(&mut system, CUTOFF, false, &evaluate_model);
+        let (half_energy, half_forces, _) = evaluate(&mut system, CUTOFF, true, &evaluate_model);
+        assert_close(half_energy, energy);
+        for (half_force, force) in half_forces.iter().zip(forces.iter()) {
+            assert!((half_force - force).norm() < 1e-9);
+        }
+        for half in [false, true] {
+            check_numerical_forces(&mut system, CUTOFF, half, &evaluate_model);
+        }
+    }
+}
@@ -0,0 +1,395 @@
+use std::f64::consts::PI;
+use std::sync::Mutex;
+
+use nalgebra::{Matrix3, Vector3};
+use rayon::prelude::*;
+use yaml_rust::Yaml;
+
+use crate::dynamics::neighbors::NeighborsList;
+use crate::io::input::to_f64;
+use crate::statics::models::accumulate_pairs;
+use crate::statics::models::bonded::{SpecialFactors, UNSCALED};
+use crate::system::SystemDefinition;
+use crate::utils::metrics::{UnitSystem, ELEMENTARY_CHARGE};
+
+// Classic Ewald summation, splitting 1/r into a short-ranged erfc(alpha r) / r evaluated over
+// the neighbors list and a smooth remainder summed over reciprocal vectors
+pub struct Ewald {
+    pub cutoff: f64,
+    // Force error relative to two elementary charges 1 Ang. apart
+    accuracy: f64,
+    // Splitting parameter and reciprocal vectors along each box vector,
+    // chosen from the accuracy when missing
+    alpha: Option<f64>,
+    kmax: Option<[i64; 3]>,
+    reciprocal_space: Mutex<Option<ReciprocalSpace>>,
+}
+
+// Parameters fixed at the first evaluation, with the reciprocal vectors of the last box seen
+struct ReciprocalSpace {
+    alpha: f64,
+    kmax: [i64; 3],
+    box_vectors: Matrix3<f64>,
+    vectors: Vec<Vector3<f64>>,
+}
+
+impl Ewald {
+    pub fn from(definition: &Yaml) -> Ewald {
+        Ewald {
+            cutoff: match &definition["cutoff"] {
+                Yaml::BadValue => panic!("Ewald summation requires a real-space cutoff"),
+                cutoff => to_f64(cutoff),
+            },
+            accuracy: match &definition["accuracy"] {
+                Yaml::BadValue => 1e-5,
+                accuracy => to_f64(accuracy),
+            },
+            alpha: match &definition["alpha"] {
+                Yaml::BadValue => None,
+                alpha => Some(to_f64(alpha)),
+            },
+            kmax: match definition["kmax"].as_vec() {
+                Some(kmax) if kmax.len() == 3 => Some([0, 1, 2].map(|i| match kmax[i].as_i64() {
+                    Some(x) if x >= 0 => x,
+                    _ => panic!("Ewald kmax must be three non-negative integers"),
+                })),
+                Some(_) => panic!("Ewald kmax must be three non-negative integers"),
+                None => None,
+            },
+            reciprocal_space: Mutex::new(None),
+        }
+    }
+    // Splitting parameter and kmax, following the error estimates of Kolafa and Perram used
+    // by LAMMPS
+    fn parameters(&self, system: &SystemDefinition, squared_charges: f64) -> (f64, [i64; 3]) {
+        let atoms_count = system.atoms.len() as f64;
+        let widths = system.simulation_box.perpendicular_widths();
+        let q2 = system.units.coulomb_constant() * squared_charges;
+        let accuracy = absolute_accuracy(&system.units, self.accuracy);
+        let alpha = match self.alpha {
+            Some(alpha) => alpha,
+            None => splitting_parameter(system, accuracy, q2, self.cutoff),
+        };
+        let kmax = match self.kmax {
+            Some(kmax) => kmax,
+            None => [0, 1, 2].map(|i| {
+                let error = |k: i64| {
+                    2.0 * q2 * alpha / widths[i]
+                        * (1.0 / (PI * k as f64 * atoms_count)).sqrt()
+                        * (-(PI * k as f64 / (alpha * widths[i])).powi(2)).exp()
+                };
+                let mut k = 1;
+                while error(k) > accuracy {
+                    k += 1;
+                }
+                k
+            }),
+        };
+        (alpha, kmax)
+    }
+    // Splitting parameter and half of the reciprocal vectors (k and -k contribute alike).
+    // Parameters are chosen at the first evaluation, the vectors being rebuilt from the same
+    // kmax whenever the box changes.
+    fn reciprocal_space(
+        &self,
+        system: &SystemDefinition,
+        squared_charges: f64,
+    ) -> (f64, Vec<Vector3<f64>>) {
+        let mut reciprocal_space = self.reciprocal_space.lock().unwrap();
+        let reciprocal_space = reciprocal_space.get_or_insert_with(|| {
+            let (alpha, kmax) = self.parameters(system, squared_charges);
+            ReciprocalSpace {
+                alpha,
+                kmax,
+                box_vectors: Matrix3::zeros(),
+                vectors: Vec::new(),
+            }
+        });
+        if reciprocal_space.box_vectors != system.simulation_box.vectors {
+            reciprocal_space.box_vectors = system.simulation_box.vectors;
+            reciprocal_space.vectors = reciprocal_vectors(system, &reciprocal_space.kmax);
+        }
+        (reciprocal_space.alpha, reciprocal_space.vectors.clone())
+    }
+    pub fn evaluate(
+        &self,
+        system: &mut SystemDefinition,
+        neighbors_list: &NeighborsList,
+        special_factors: &SpecialFactors,
+    ) {
+        let squared_charges = squared_charges(system);
+        if squared_charges == 0.0 {
+            return;
+        }
+        let coulomb_constant = system.units.coulomb_constant();
+        let volume = system.simulation_box.volume();
+        let (alpha, vectors) = self.reciprocal_space(system, squared_charges);
+        add_direct_terms(system, neighbors_list, alpha, self.cutoff, special_factors);
+
+        // Reciprocal space: sum_k c_k |S(k)|^2 over half of the vectors, with
+        //   c_k = 4 pi / V exp(-k^2 / (4 alpha^2)) / k^2 and S(k) = sum_j q_j exp(i k.r_j)
+        let terms = vectors
+            .par_iter()
+            .map(|k| {
+                let (cosines, sines) = system.atoms.iter().fold((0.0, 0.0), |(c, s), atom| {
+                    let phase = k.dot(&atom.current.position);
+                    (c + atom.charge * phase.cos(), s + atom.charge * phase.sin())
+                });
+                let k2 = k.norm_squared();
+                let coefficient =
+                    4.0 * PI * coulomb_constant / volume * (-k2 / (4.0 * alpha.powi(2))).exp() / k2;
+                // Strain derivative of the term, giving its virial
+                let virial = Matrix3::identity()
+                    - 2.0 * (1.0 / k2 + 1.0 / (4.0 * alpha.powi(2))) * k * k.transpose();
+                (coefficient, cosines, sines, virial)
+            })
+            .collect::<Vec<(f64, f64, f64, Matrix3<f64>)>>();
+        system.atoms.par_iter_mut().for_each(|atom| {
+            let charge = atom.charge;
+            for (k, (coefficient, cosines, sines, virial)) in vectors.iter().zip(terms.iter()) {
+                let phase = k.dot(&atom.current.position);
+                let (cosine, sine) = (phase.cos(), phase.sin());
+                let energy = coefficient * charge * (cosine * cosines + sine * sines);
+                atom.current.potential_energy += energy;
+                atom.current.force +=
+                    2.0 * coefficient * charge * (sine * cosines - cosine * sines) * k;
+                atom.current.virial += energy * virial;
+            }
+        });
+    }
+}
+
+// Half of the reciprocal vectors within a sphere covering kmax vectors along every axis
+fn reciprocal_vectors(system: &SystemDefinition, kmax: &[i64; 3]) -> Vec<Vector3<f64>> {
+    let widths = system.simulation_box.perpendicular_widths();
+    let cutoff_squared = (0..3)
+        .map(|i| (2.0 * PI * kmax[i] as f64 / widths[i]).powi(2))
+        .fold(0.0, f64::max)
+        * 1.00001;
+    // k = 2 pi F^T n, F mapping cartesian to fractional coordinates
+    let reciprocal = 2.0 * PI * system.simulation_box.fractional_matrix.transpose();
+    let bounds = (0..3)
+        .map(|i| {
+            let length = system.simulation_box.vectors.row(i).norm();
+            (cutoff_squared.sqrt() * length / (2.0 * PI)).floor() as i64
+        })
+        .collect::<Vec<i64>>();
+    let mut vectors = Vec::new();
+    for nx in 0..=bounds[0] {
+        for ny in -bounds[1]..=bounds[1] {
+            for nz in -bounds[2]..=bounds[2] {
+                // Keep one of each k, -k couple
+                if nx == 0 && (ny < 0 || (ny == 0 && nz <= 0)) {
+                    continue;
+                }
+                let k = reciprocal * Vector3::new(nx as f64, ny as f64, nz as f64);
+                if k.norm_squared() <= cutoff_squared {
+                    vectors.push(k);
+                }
+            }
+        }
+    }
+    vectors
+}
+
+// Sum of the squared charges, checking first that the system suits lattice sums
+pub fn squared_charges(system: &SystemDefinition) -> f64 {
+    if system.simulation_box.periodicity != [true, true, true] {
+        panic!("Ewald summations require a fully periodic system");
+    }
+    system.atoms.iter().map(|atom| atom.charge.powi(2)).sum()
+}
+
+// Force accuracy in system units, given relative to two elementary charges 1 Ang. apart
+pub fn absolute_accuracy(units: &UnitSystem, accuracy: f64) -> f64 {
+    accuracy * units.coulomb_constant() * (ELEMENTARY_CHARGE / units.charge.0).powi(2)
+        / (1e-10 / units.distance.0).powi(2)
+}
+
+// Splitting parameter bringing the real-space force error down to the accuracy, with q2 the
+// sum of squared charges times the Coulomb constant
+pub fn splitting_parameter(system: &SystemDefinition, accuracy: f64, q2: f64, cutoff: f64) -> f64 {
+    let atoms_count = system.atoms.len() as f64;
+    let volume = system.simulation_box.volume();
+    let estimate = accuracy * (atoms_count * cutoff * volume).sqrt() / (2.0 * q2);
+    match estimate >= 1.0 {
+        true => (1.35 - 0.15 * accuracy.ln()) / cutoff,
+        false => (-estimate.ln()).sqrt() / cutoff,
+    }
+}
+
+// Real-space pairs, self energy and neutralizing background, shared by every Ewald method
+pub fn add_direct_terms(
+    system: &mut SystemDefinition,
+    neighbors_list: &NeighborsList,
+    alpha: f64,
+    cutoff: f64,
+    special_factors: &SpecialFactors,
+) {
+    let charges = system
+        .atoms
+        .iter()
+        .map(|atom| atom.charge)
+        .collect::<Vec<f64>>();
+    let total_charge = charges.iter().sum::<f64>();
+    let coulomb_constant = system.units.coulomb_constant();
+    let volume = system.simulation_box.volume();
+
+    // Real space: q_i q_j erfc(alpha r) / r
+    accumulate_pairs(
+        &mut system.atoms,
+        neighbors_list,
+        &|i, j, r| {
+            if r >= cutoff {
+                return (0.0, 0.0);
+            }
+            let charges_product = coulomb_constant * charges[i] * charges[j];
+            let screened = libm::erfc(alpha * r) / r;
+            (
+                charges_product * screened,
+                charges_product
+                    * (screened + 2.0 * alpha / PI.sqrt() * (-(alpha * r).powi(2)).exp())
+                    / r,
+            )
+        },
+        &UNSCALED,
+    );
+    // The reciprocal sum covers every pair, so the scaled-down part of bonded pairs is
+    // taken back from the full q_i q_j / r
+    let removed_factors = special_factors.map(|x| 1.0 - x);
+    if removed_factors[1..].iter().any(|x| *x != 0.0) {
+        accumulate_pairs(
+            &mut system.atoms,
+            neighbors_list,
+            &|i, j, r| {
+                let charges_product = coulomb_constant * charges[i] * charges[j];
+                (-charges_product / r, -charges_product / r.powi(2))
+            },
+            &[
+                0.0,
+                removed_factors[1],
+                removed_factors[2],
+                removed_factors[3],
+            ],
+        );
+    }
+
+    let self_energy = coulomb_constant * alpha / PI.sqrt();
+    // Energy of a uniform background cancelling a net charge
+    let background_energy = -coulomb_constant * PI * total_charge / (2.0 * volume * alpha.powi(2));
+    system.atoms.par_iter_mut().for_each(|atom| {
+        let charge = atom.charge;
+        atom.current.potential_energy += -self_energy * charge.powi(2) + background_energy * charge;
+        atom.current.virial += Matrix3::identity() * background_energy * charge;
+    });
+}
+
+impl std::fmt::Display for Ewald {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Ewald summation with real-space cutoff {} and accuracy {}",
+            self.cutoff, self.accuracy
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::statics::models::tests::{check_numerical_forces, evaluate, system};
+
+    const MADELUNG_CONSTANT: f64 = 1.747565;
+    // Nearest-neighbor distance of rock salt
+    const DISTANCE: f64 = 2.82;
+
+    fn ewald(definition: &str) -> Ewald {
+        Ewald::from(&yaml_rust::YamlLoader::load_from_str(definition).unwrap()[0])
+    }
+
+    fn energy(system: &mut SystemDefinition, ewald: &Ewald) -> f64 {
+        evaluate(system, ewald.cutoff, true, &|system, neighbors_list| {
+            ewald.evaluate(system, neighbors_list, &UNSCALED)
+        })
+        .0
+    }
+
+    fn check_madelung_energy(system: &mut SystemDefinition) {
+        let ewald = ewald("{cutoff: 5.0, accuracy: 1e-7}");
+        let pairs = system.atoms.len() as f64 / 2.0;
+        let expected = -pairs * MADELUNG_CONSTANT * system.units.coulomb_constant() / DISTANCE;
+        let energy = energy(system, &ewald);
+        assert!(
+            (energy - expected).abs() < 1e-5 * expected.abs(),
+            "energy {} instead of {}",
+            energy,
+            expected
+        );
+    }
+
+    #[test]
+    fn conventional_rock_salt_gives_madelung_energy() {
+        let mut system = system(&format!(
+            "
+            cell: [[{a}, 0, 0], [0, {a}, 0], [0, 0, {a}]]
+            periodicity: xyz
+            units: atomic
+            replicas: [2, 2, 2]
+            atoms:
+              - {{name: Na, charge: 1, position: [0.0, 0.0, 0.0]}}
+              - {{name: Na, charge: 1, position: [0.0, 0.5, 0.5]}}
+              - {{name: Na, charge: 1, position: [0.5, 0.0, 0.5]}}
+              - {{name: Na, charge: 1, position: [0.5, 0.5, 0.0]}}
+              - {{name: Cl, charge: -1, position: [0.5, 0.0, 0.0]}}
+              - {{name: Cl, charge: -1, position: [0.0, 0.5, 0.0]}}
+              - {{name: Cl, charge: -1, position: [0.0, 0.0, 0.5]}}
+              - {{name: Cl, charge: -1, position: [0.5, 0.5, 0.5]}}
+            ",
+            a = 2.0 * DISTANCE
+        ));
+        assert_eq!(system.atoms.len(), 64);
+        check_madelung_energy(&mut system);
+    }
+
+    #[test]
+    fn primitive_rock_salt_gives_madelung_energy() {
+        let mut system = system(&format!(
+            "
+            cell: [[0, {d}, {d}], [{d}, 0, {d}], [{d}, {d}, 0]]
+            periodicity: xyz
+            units: atomic
+            replicas: [4, 4, 4]
+            atoms:
+              - {{name: Na, charge: 1, position: [0.0, 0.0, 0.0]}}
+              - {{name: Cl, charge: -1, position: [0.5, 0.5, 0.5]}}
+            ",
+            d = DISTANCE
+        ));
+        assert_eq!(system.atoms.len(), 128);
+        check_madelung_energy(&mut system);
+    }
+
+    #[test]
+    fn forces_match_energy_derivatives() {
+        let ewald = ewald("{cutoff: 4.5, accuracy: 1e-8}");
+        let mut system = system(
+            "
+            cell: [[10.0, 0, 0], [2.0, 9.5, 0], [-1.5, 1.0, 10.5]]
+            periodicity: xyz
+            units: atomic
+            atoms:
+              - {name: Na, charge: 1.0, position: [0.10, 0.15, 0.20]}
+              - {name: Cl, charge: -0.8, position: [0.35, 0.22, 0.14]}
+              - {name: Na, charge: 0.6, position: [0.62, 0.71, 0.55]}
+              - {name: Cl, charge: -1.2, position: [0.84, 0.40, 0.77]}
+              - {name: Na, charge: 0.3, position: [0.27, 0.88, 0.61]}
+            ",
+        );
+        check_numerical_forces(
+            &mut system,
+            ewald.cutoff,
+            true,
+            &|system, neighbors_list| ewald.evaluate(system, neighbors_list, &UNSCALED),
+        );
+    }
+}
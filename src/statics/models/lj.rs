@@ -1,24 +1,25 @@
+use crate::io::input::to_f64;
+use crate::statics::models::pair_table::{MixingRule, PairTable};
+use crate::statics::models::truncation::{parse_tail_correction, Truncation};
 use crate::statics::models::CalculatePotential;
 use yaml_rust::Yaml;
 
-pub struct LennardJonesModel {
+#[derive(Clone)]
+pub struct LennardJonesPair {
     epsilon: f64,
     sigma: f64,
     cutoff: f64,
 }
 
-impl LennardJonesModel {
-    pub fn initialize(definition: &Yaml) -> LennardJonesModel {
-        LennardJonesModel {
-            epsilon: definition["parameters"]["epsilon"].as_f64().unwrap(),
-            sigma: definition["parameters"]["sigma"].as_f64().unwrap(),
+impl LennardJonesPair {
+    fn from(definition: &Yaml, default_cutoff: Option<f64>) -> LennardJonesPair {
+        let sigma = to_f64(&definition["sigma"]);
+        LennardJonesPair {
+            epsilon: to_f64(&definition["epsilon"]),
+            sigma,
             cutoff: match &definition["cutoff"] {
-                Yaml::Real(cutoff) => match cutoff.parse::<f64>() {
-                    Ok(cutoff) => cutoff,
-                    Err(_) => panic!("Cutoff must be a real number"),
-                },
-                Yaml::BadValue => definition["parameters"]["sigma"].as_f64().unwrap() * 2.5,
-                _ => panic!("Cutoff must be a real number"),
+                Yaml::BadValue => default_cutoff.unwrap_or(sigma * 2.5),
+                cutoff => to_f64(cutoff),
             },
         }
     }
@@ -31,32 +32,90 @@ impl LennardJonesModel {
     }
 }
 
-impl CalculatePotential for LennardJonesModel {
+pub struct LennardJonesModel {
+    pub pairs: PairTable<LennardJonesPair>,
+    pub truncation: Truncation,
+    pub tail_correction: bool,
+    mixing: MixingRule,
+}
+
+impl LennardJonesModel {
+    pub fn initialize(definition: &Yaml) -> LennardJonesModel {
+        let default_cutoff = match &definition["cutoff"] {
+            Yaml::BadValue => None,
+            cutoff => Some(to_f64(cutoff)),
+        };
+        let mixing = MixingRule::from(&definition["mixing"]);
+        let model = LennardJonesModel {
+            pairs: PairTable::from(
+                definition,
+                &|yaml| LennardJonesPair::from(yaml, default_cutoff),
+                Some(&|first: &LennardJonesPair, second: &LennardJonesPair| {
+                    let sigma = mixing.mix_length(first.sigma, second.sigma);
+                    LennardJonesPair {
+                        epsilon: mixing.mix_energy(first.epsilon, second.epsilon),
+                        sigma,
+                        cutoff: default_cutoff.unwrap_or(sigma * 2.5),
+                    }
+                }),
+            ),
+            truncation: Truncation::from(definition),
+            tail_correction: parse_tail_correction(definition),
+            mixing,
+        };
+        model
+            .pairs
+            .values()
+            .for_each(|pair| model.truncation.check_cutoff(pair.cutoff));
+        model
+    }
+    pub fn max_cutoff(&self) -> f64 {
+        self.pairs.values().map(|x| x.cutoff).fold(0.0, f64::max)
+    }
+}
+
+impl std::fmt::Display for LennardJonesModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Lennard-Jones with {} mixing and {}",
+            self.mixing, self.truncation
+        )
+    }
+}
+
+impl CalculatePotential for LennardJonesPair {
     fn calculate_potential(&self, distance: f64) -> f64 {
-        match distance < self.cutoff {
-            true => match distance > 0.0 {
-                true => self.calculate_potential_at_distance(distance),
-                // Lennard-Jones potential is infinite at r = 0
-                false => f64::INFINITY,
-            },
-            // Lennard-Jones potential is 0 at r > cutoff
-            false => 0.0,
+        match distance > 0.0 {
+            true => self.calculate_potential_at_distance(distance),
+            // Lennard-Jones potential is infinite at r = 0
+            false => f64::INFINITY,
         }
     }
     fn calculate_force(&self, distance: f64) -> f64 {
-        match distance < self.cutoff {
-            true => match distance > 0.0 {
-                true => {
-                    let r6 = distance.powi(6);
-                    let r12 = distance.powi(12);
-                    (24.0 * self.epsilon)
-                        * ((2.0 * self.sigma.powi(12) / r12) - (self.sigma.powi(6) / r6))
-                }
-                // Lennard-Jones potential is infinite at r = 0
-                false => f64::INFINITY,
-            },
-            // Lennard-Jones potential is 0 at r > cutoff
-            false => 0.0,
+        match distance > 0.0 {
+            true => {
+                let r6 = distance.powi(6);
+                let r12 = distance.powi(12);
+                // F = -dV/dr = 24 epsilon / r * (2 (sigma / r)^12 - (sigma / r)^6)
+                (24.0 * self.epsilon / distance)
+                    * ((2.0 * self.sigma.powi(12) / r12) - (self.sigma.powi(6) / r6))
+            }
+            // Lennard-Jones potential is infinite at r = 0
+            false => f64::INFINITY,
         }
     }
+    fn cutoff(&self) -> f64 {
+        self.cutoff
+    }
+    fn tail_integrals(&self) -> Option<(f64, f64)> {
+        let sigma6 = self.sigma.powi(6);
+        let sigma12 = sigma6.powi(2);
+        let rc3 = self.cutoff.powi(3);
+        let rc9 = rc3.powi(3);
+        Some((
+            4.0 * self.epsilon * (sigma12 / (9.0 * rc9) - sigma6 / (3.0 * rc3)),
+            4.0 * self.epsilon * (-4.0 * sigma12 / (3.0 * rc9) + 2.0 * sigma6 / rc3),
+        ))
+    }
 }
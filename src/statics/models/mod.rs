@@ -1,50 +1,688 @@
+mod bonded;
+mod buckingham;
+mod coulomb;
+mod cutoff_coulomb;
+mod eam;
+mod ewald;
 mod lj;
+mod morse;
+mod pair_table;
+mod pme;
+mod table;
+mod truncation;
 
-use nalgebra::Vector3;
+use std::sync::Mutex;
 
-use crate::dynamics::neighbors::NeighborsList;
+use nalgebra::{Matrix3, Vector3};
+use rayon::prelude::*;
+
+use crate::dynamics::neighbors::{NeighborsList, NeighborsListEntry};
+use crate::statics::models::bonded::SpecialFactors;
+use crate::statics::models::pair_table::{PairTable, SpeciesSelection};
+use crate::statics::models::truncation::{apply_tail_corrections, Truncation};
 use crate::system::atom::Atom;
+use crate::system::SystemDefinition;
 
-pub enum PotentialModel {
-    LennardJones(lj::LennardJonesModel),
+// Sum of short-ranged terms, each possibly restricted to some species pairs, of bonded terms
+// over the topology and of long-ranged electrostatics. Any part may be left out.
+pub struct PotentialModel {
+    short_range: Vec<PotentialTerm>,
+    bonded: Option<bonded::BondedModel>,
+    electrostatics: Option<coulomb::Electrostatics>,
+    special_bonds: bonded::SpecialBonds,
+    // Energy of every term at the last evaluation: short-ranged ones, then bonds, angles,
+    // dihedrals and impropers, then electrostatics
+    energies: Mutex<Vec<f64>>,
+    // Species index of every atom and number of species, set up by initialize
+    species: Vec<usize>,
+    species_count: usize,
+}
+
+// Names of the bonded terms in the energy breakdown
+const BONDED_TERMS: [&str; 4] = ["bonds", "angles", "dihedrals", "impropers"];
+
+// One short-ranged model of the sum, named for the energy breakdown in the logs
+struct PotentialTerm {
+    name: String,
+    model: ShortRangeModel,
+    selection: SpeciesSelection,
+}
+
+impl PotentialTerm {
+    fn from(definition: &yaml_rust::Yaml) -> PotentialTerm {
+        PotentialTerm {
+            name: match (definition["name"].as_str(), definition["model"].as_str()) {
+                (Some(name), _) | (None, Some(name)) => name.to_string(),
+                (None, None) => panic!("Potential model must be a string"),
+            },
+            model: ShortRangeModel::from(definition),
+            selection: SpeciesSelection::from(&definition["species_pairs"]),
+        }
+    }
+}
+
+impl std::fmt::Display for PotentialTerm {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.selection.is_complete() {
+            true => write!(f, "{}", self.model),
+            false => write!(f, "{} restricted to {}", self.model, self.selection),
+        }
+    }
+}
+
+impl std::fmt::Display for PotentialModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let terms = self
+            .short_range
+            .iter()
+            .map(|x| x.to_string())
+            .chain(self.bonded.iter().map(|x| x.to_string()))
+            .chain(self.electrostatics.iter().map(|x| x.to_string()))
+            .collect::<Vec<String>>();
+        write!(f, "{}", terms.join(" plus "))
+    }
 }
 
 impl PotentialModel {
     pub fn from(potential_definition: &yaml_rust::Yaml) -> PotentialModel {
+        let short_range = match (
+            &potential_definition["model"],
+            &potential_definition["models"],
+        ) {
+            (yaml_rust::Yaml::BadValue, yaml_rust::Yaml::BadValue) => Vec::new(),
+            (_, yaml_rust::Yaml::BadValue) => vec![PotentialTerm::from(potential_definition)],
+            (yaml_rust::Yaml::BadValue, yaml_rust::Yaml::Array(models)) => {
+                models.iter().map(PotentialTerm::from).collect()
+            }
+            (yaml_rust::Yaml::BadValue, _) => panic!("Potential models must be a list"),
+            _ => panic!("Potential takes either a single model or a list of models"),
+        };
+        let bonded = match &potential_definition["bonded"] {
+            yaml_rust::Yaml::BadValue => None,
+            bonded => Some(bonded::BondedModel::from(bonded)),
+        };
+        let electrostatics = match &potential_definition["coulomb"] {
+            yaml_rust::Yaml::BadValue => None,
+            coulomb => Some(coulomb::Electrostatics::from(coulomb)),
+        };
+        if short_range.is_empty() && bonded.is_none() && electrostatics.is_none() {
+            panic!("Potential requires a model, a bonded section or a coulomb section");
+        }
+        let mut names = short_range
+            .iter()
+            .map(|term| term.name.as_str())
+            .chain(bonded.iter().flat_map(|_| BONDED_TERMS))
+            .chain(electrostatics.iter().map(|_| "coulomb"))
+            .collect::<Vec<&str>>();
+        let terms_count = names.len();
+        names.sort();
+        names.dedup();
+        if names.len() != terms_count {
+            panic!("Potential terms must have distinct names, set with `name`");
+        }
+        PotentialModel {
+            short_range,
+            bonded,
+            electrostatics,
+            special_bonds: bonded::SpecialBonds::from(&potential_definition["special_bonds"]),
+            energies: Mutex::new(vec![0.0; terms_count]),
+            species: Vec::new(),
+            species_count: 0,
+        }
+    }
+    // Largest distance at which any pair of atoms still interacts directly
+    pub fn max_cutoff(&self) -> f64 {
+        let short_range_cutoff = self
+            .short_range
+            .iter()
+            .map(|term| term.model.max_cutoff())
+            .fold(0.0, f64::max);
+        match &self.electrostatics {
+            Some(electrostatics) => short_range_cutoff.max(electrostatics.cutoff()),
+            None => short_range_cutoff,
+        }
+    }
+    // Checks that every bonded type of the topology has coefficients and maps the atom names
+    // to species indices, so that pair coefficients are then looked up by index
+    pub fn initialize(&mut self, system: &SystemDefinition) {
+        match &self.bonded {
+            Some(bonded) => bonded.check_topology(&system.topology),
+            None if !system.topology.is_empty() => {
+                panic!("The system topology requires a bonded section in the potential")
+            }
+            None => (),
+        }
+        let mut names = system
+            .atoms
+            .iter()
+            .map(|atom| atom.name.clone())
+            .collect::<Vec<String>>();
+        names.sort();
+        names.dedup();
+        self.species = system
+            .atoms
+            .iter()
+            .map(|atom| names.binary_search(&atom.name).unwrap())
+            .collect();
+        self.species_count = names.len();
+        for term in &mut self.short_range {
+            term.selection.index_species(&names);
+            term.model.index_species(&names, &term.selection);
+        }
+    }
+    // Energy of the named term at the last evaluation, bonded terms being named bonds, angles,
+    // dihedrals and impropers and electrostatics coulomb
+    pub fn term_energy(&self, name: &str) -> Option<f64> {
+        self.term_index(name)
+            .map(|index| self.energies.lock().unwrap()[index])
+    }
+    pub fn has_term(&self, name: &str) -> bool {
+        self.term_index(name).is_some()
+    }
+    fn term_index(&self, name: &str) -> Option<usize> {
+        let bonded_count = match self.bonded {
+            Some(_) => BONDED_TERMS.len(),
+            None => 0,
+        };
+        match self.short_range.iter().position(|term| term.name == name) {
+            Some(index) => Some(index),
+            None => match BONDED_TERMS.iter().position(|x| *x == name) {
+                Some(index) if bonded_count > 0 => Some(self.short_range.len() + index),
+                _ if name == "coulomb" && self.electrostatics.is_some() => {
+                    Some(self.short_range.len() + bonded_count)
+                }
+                _ => None,
+            },
+        }
+    }
+    pub fn evaluate(&self, system: &mut SystemDefinition, neighbors_list: &NeighborsList) {
+        if self.species.len() != system.atoms.len() {
+            panic!("Potential model must be initialized with the system before evaluation");
+        }
+        // Every term adds to the energies, forces and virials, starting from zero
+        system.atoms.par_iter_mut().for_each(|atom| {
+            atom.current.potential_energy = 0.0;
+            atom.current.force = Vector3::zeros();
+            atom.current.virial = Matrix3::zeros();
+        });
+        // Term energies are told apart by the growth of the total after each term
+        let total_energy = |system: &SystemDefinition| {
+            system
+                .atoms
+                .par_iter()
+                .map(|atom| atom.current.potential_energy)
+                .sum::<f64>()
+        };
+        let mut energies = Vec::with_capacity(self.short_range.len() + BONDED_TERMS.len() + 1);
+        let mut previous_total = 0.0;
+        for term in &self.short_range {
+            let scope = PairScope {
+                species: &self.species,
+                species_count: self.species_count,
+                selection: &term.selection,
+                special_factors: &self.special_bonds.short_range,
+            };
+            term.model.evaluate(system, neighbors_list, &scope);
+            let total = total_energy(system);
+            energies.push(total - previous_total);
+            previous_total = total;
+        }
+        if let Some(bonded) = &self.bonded {
+            let bonded_energies = bonded.evaluate(system);
+            energies.extend(bonded_energies);
+            previous_total += bonded_energies.iter().sum::<f64>();
+        }
+        if let Some(electrostatics) = &self.electrostatics {
+            electrostatics.evaluate(system, neighbors_list, &self.special_bonds.coulomb);
+            energies.push(total_energy(system) - previous_total);
+        }
+        *self.energies.lock().unwrap() = energies;
+    }
+}
+
+// Short-ranged interactions, evaluated over the neighbors list
+pub enum ShortRangeModel {
+    LennardJones(lj::LennardJonesModel),
+    Morse(morse::MorseModel),
+    Buckingham(buckingham::BuckinghamModel),
+    Tabulated(table::TabulatedModel),
+    EmbeddedAtom(eam::EamModel),
+}
+
+impl std::fmt::Display for ShortRangeModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ShortRangeModel::LennardJones(x) => write!(f, "{}", x),
+            ShortRangeModel::Morse(x) => write!(f, "{}", x),
+            ShortRangeModel::Buckingham(x) => write!(f, "{}", x),
+            ShortRangeModel::Tabulated(x) => write!(f, "{}", x),
+            ShortRangeModel::EmbeddedAtom(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl ShortRangeModel {
+    pub fn from(potential_definition: &yaml_rust::Yaml) -> ShortRangeModel {
         match &potential_definition["model"] {
             yaml_rust::Yaml::String(potential) => match potential.as_str() {
-                "lj" => PotentialModel::LennardJones(lj::LennardJonesModel::initialize(
+                "lj" => ShortRangeModel::LennardJones(lj::LennardJonesModel::initialize(
                     &potential_definition,
                 )),
+                "morse" => {
+                    ShortRangeModel::Morse(morse::MorseModel::initialize(potential_definition))
+                }
+                "buckingham" => {
+                    ShortRangeModel::Buckingham(buckingham::BuckinghamModel::initialize(
+                        potential_definition,
+                        buckingham::BuckinghamForm::Buckingham,
+                    ))
+                }
+                "born_mayer_huggins" | "bmh" => {
+                    ShortRangeModel::Buckingham(buckingham::BuckinghamModel::initialize(
+                        potential_definition,
+                        buckingham::BuckinghamForm::BornMayerHuggins,
+                    ))
+                }
+                "table" => ShortRangeModel::Tabulated(table::TabulatedModel::initialize(
+                    potential_definition,
+                )),
+                "eam" => {
+                    ShortRangeModel::EmbeddedAtom(eam::EamModel::initialize(potential_definition))
+                }
                 _ => panic!("Potential model not implemented"),
             },
-            _ => panic!("Potential model not implemented"),
-        }
-    }
-    pub fn update(&self, atom: &mut Atom, neighbors_list: &NeighborsList) -> () {
-        atom.current.potential_energy = 0.0;
-        atom.current.force = Vector3::new(0.0, 0.0, 0.0);
-        neighbors_list
-            .get_neighbors(atom.id as u64)
-            .iter_mut()
-            .for_each(|neighbor| {
-                let current_pair_potential_energy = match self {
-                    PotentialModel::LennardJones(model) => {
-                        model.calculate_potential(neighbor.distance)
-                    }
-                };
-                atom.current.potential_energy += current_pair_potential_energy;
-                let force = match self {
-                    PotentialModel::LennardJones(model) => {
-                        model.calculate_force(neighbor.distance)
-                    }
-                };
-                atom.current.force += -force * neighbor.distance_vector.normalize();
-            });
+            _ => panic!("Potential model must be a string"),
+        }
     }
+    fn index_species(&mut self, species: &[String], selection: &SpeciesSelection) {
+        match self {
+            ShortRangeModel::LennardJones(model) => model.pairs.index_species(species),
+            ShortRangeModel::Morse(model) => model.pairs.index_species(species),
+            ShortRangeModel::Buckingham(model) => model.pairs.index_species(species),
+            ShortRangeModel::Tabulated(model) => model.pairs.index_species(species),
+            ShortRangeModel::EmbeddedAtom(model) => model.index_species(species, selection),
+        }
+    }
+    // Largest distance at which any pair of species still interacts
+    pub fn max_cutoff(&self) -> f64 {
+        match self {
+            ShortRangeModel::LennardJones(model) => model.max_cutoff(),
+            ShortRangeModel::Morse(model) => model.max_cutoff(),
+            ShortRangeModel::Buckingham(model) => model.max_cutoff(),
+            ShortRangeModel::Tabulated(model) => model.max_cutoff(),
+            ShortRangeModel::EmbeddedAtom(model) => model.max_cutoff(),
+        }
+    }
+    fn evaluate(
+        &self,
+        system: &mut SystemDefinition,
+        neighbors_list: &NeighborsList,
+        scope: &PairScope,
+    ) {
+        match self {
+            ShortRangeModel::LennardJones(model) => evaluate_pair_model(
+                system,
+                neighbors_list,
+                scope,
+                &model.pairs,
+                &model.truncation,
+                model.tail_correction,
+            ),
+            ShortRangeModel::Morse(model) => evaluate_pair_model(
+                system,
+                neighbors_list,
+                scope,
+                &model.pairs,
+                &model.truncation,
+                model.tail_correction,
+            ),
+            ShortRangeModel::Buckingham(model) => evaluate_pair_model(
+                system,
+                neighbors_list,
+                scope,
+                &model.pairs,
+                &model.truncation,
+                model.tail_correction,
+            ),
+            ShortRangeModel::Tabulated(model) => evaluate_pair_model(
+                system,
+                neighbors_list,
+                scope,
+                &model.pairs,
+                &model.truncation,
+                false,
+            ),
+            ShortRangeModel::EmbeddedAtom(model) => model.evaluate(
+                &mut system.atoms,
+                neighbors_list,
+                scope.species,
+                scope.selection,
+            ),
+        }
+    }
+}
+
+// Species index of every atom, pairs of species a term applies to and scaling of the bonded
+// pairs
+struct PairScope<'a> {
+    species: &'a [usize],
+    species_count: usize,
+    selection: &'a SpeciesSelection,
+    special_factors: &'a SpecialFactors,
+}
+
+// Evaluates a pair potential with per-pair coefficients under the given truncation scheme
+fn evaluate_pair_model<T: CalculatePotential + Clone + Sync>(
+    system: &mut SystemDefinition,
+    neighbors_list: &NeighborsList,
+    scope: &PairScope,
+    pairs: &PairTable<T>,
+    truncation: &Truncation,
+    tail_correction: bool,
+) {
+    let PairScope {
+        species,
+        species_count,
+        selection,
+        special_factors,
+    } = scope;
+    accumulate_pairs(
+        &mut system.atoms,
+        neighbors_list,
+        &|i, j, distance| match selection.includes_pair(species[i], species[j]) {
+            true => truncation.evaluate(pairs.get_indexed(species[i], species[j]), distance),
+            false => (0.0, 0.0),
+        },
+        special_factors,
+    );
+    if tail_correction {
+        if system.simulation_box.periodicity != [true, true, true] {
+            panic!("Tail corrections require a fully periodic system");
+        }
+        let volume = system.simulation_box.volume();
+        apply_tail_corrections(
+            &mut system.atoms,
+            species,
+            *species_count,
+            volume,
+            &|first, second| {
+                if !selection.includes_pair(first, second) {
+                    return (0.0, 0.0);
+                }
+                pairs
+                    .get_indexed(first, second)
+                    .tail_integrals()
+                    .unwrap_or((0.0, 0.0))
+            },
+        );
+    }
+}
+
+// Pair energy and force magnitude between atoms i and j at the given distance,
+// the force being positive when repulsive. It must be symmetric in i and j.
+type PairInteraction<'a> = dyn Fn(usize, usize, f64) -> (f64, f64) + Sync + 'a;
+
+// Adds the pair interactions to the energies, forces and virials of every atom, scaled by
+// the factor of their bond separation. Pairs with a zero factor are not evaluated.
+fn accumulate_pairs(
+    atoms: &mut [Atom],
+    neighbors_list: &NeighborsList,
+    interaction: &PairInteraction,
+    special_factors: &SpecialFactors,
+) {
+    let scaled_interaction = |neighbor: &NeighborsListEntry, index: usize| {
+        let factor = special_factors[neighbor.separation as usize];
+        if factor == 0.0 {
+            return None;
+        }
+        let (energy, force) = interaction(index, neighbor.index as usize, neighbor.distance);
+        Some((factor * energy, factor * force))
+    };
+    match neighbors_list.half {
+        true => update_with_half_list(atoms, neighbors_list, &scaled_interaction),
+        false => atoms
+            .par_iter_mut()
+            .for_each(|atom| update_atom(atom, neighbors_list, &scaled_interaction)),
+    }
+}
+
+// Pair interaction of an atom with one of its neighbors, None when left out
+type NeighborInteraction<'a> = dyn Fn(&NeighborsListEntry, usize) -> Option<(f64, f64)> + Sync + 'a;
+
+fn update_atom(atom: &mut Atom, neighbors_list: &NeighborsList, interaction: &NeighborInteraction) {
+    let index = atom.id as usize;
+    neighbors_list
+        .get_neighbors(atom.id)
+        .iter()
+        .for_each(|neighbor| {
+            let (pair_potential_energy, force) = match interaction(neighbor, index) {
+                Some(x) => x,
+                None => return,
+            };
+            // Every pair is visited from both atoms, so each one keeps half of the pair energy
+            atom.current.potential_energy += 0.5 * pair_potential_energy;
+            let pair_force = -force * neighbor.distance_vector.normalize();
+            atom.current.force += pair_force;
+            // Pair virial r_ij (x) f_ij with r_ij pointing from the neighbor to this atom,
+            // split evenly between both atoms of the pair like the energy
+            atom.current.virial += 0.5 * (-neighbor.distance_vector) * pair_force.transpose();
+        });
+}
+
+// Each pair of a half list is evaluated once and applied to both atoms. Threads accumulate
+// into their own buffers, which are summed up at the end.
+fn update_with_half_list(
+    atoms: &mut [Atom],
+    neighbors_list: &NeighborsList,
+    interaction: &NeighborInteraction,
+) {
+    let atoms_count = atoms.len();
+    let contributions = (0..atoms_count)
+        .into_par_iter()
+        .fold(
+            || PairContributions::zeros(atoms_count),
+            |mut contributions, index| {
+                neighbors_list
+                    .get_neighbors(index as u64)
+                    .iter()
+                    .for_each(|neighbor| {
+                        let neighbor_index = neighbor.index as usize;
+                        let (pair_potential_energy, force) = match interaction(neighbor, index) {
+                            Some(x) => x,
+                            None => return,
+                        };
+                        let pair_force = -force * neighbor.distance_vector.normalize();
+                        let pair_virial =
+                            0.5 * (-neighbor.distance_vector) * pair_force.transpose();
+                        contributions.add(
+                            index,
+                            0.5 * pair_potential_energy,
+                            pair_force,
+                            pair_virial,
+                        );
+                        contributions.add(
+                            neighbor_index,
+                            0.5 * pair_potential_energy,
+                            -pair_force,
+                            pair_virial,
+                        );
+                    });
+                contributions
+            },
+        )
+        .reduce(
+            || PairContributions::zeros(atoms_count),
+            PairContributions::merge,
+        );
+    atoms.par_iter_mut().enumerate().for_each(|(index, atom)| {
+        atom.current.potential_energy += contributions.potential_energies[index];
+        atom.current.force += contributions.forces[index];
+        atom.current.virial += contributions.virials[index];
+    });
 }
 
+// Sums a scalar over the neighbors of every atom, contribution(i, j, r) being what atom i
+// receives from its neighbor j. Pairs of a half list give to both of their atoms.
+fn accumulate_pair_scalars(
+    atoms_count: usize,
+    neighbors_list: &NeighborsList,
+    contribution: &(dyn Fn(usize, usize, f64) -> f64 + Sync),
+) -> Vec<f64> {
+    match neighbors_list.half {
+        false => (0..atoms_count)
+            .into_par_iter()
+            .map(|index| {
+                neighbors_list
+                    .get_neighbors(index as u64)
+                    .iter()
+                    .map(|neighbor| contribution(index, neighbor.index as usize, neighbor.distance))
+                    .sum()
+            })
+            .collect(),
+        true => (0..atoms_count)
+            .into_par_iter()
+            .fold(
+                || vec![0.0; atoms_count],
+                |mut totals, index| {
+                    neighbors_list
+                        .get_neighbors(index as u64)
+                        .iter()
+                        .for_each(|neighbor| {
+                            let neighbor_index = neighbor.index as usize;
+                            totals[index] += contribution(index, neighbor_index, neighbor.distance);
+                            totals[neighbor_index] +=
+                                contribution(neighbor_index, index, neighbor.distance);
+                        });
+                    totals
+                },
+            )
+            .reduce(
+                || vec![0.0; atoms_count],
+                |mut first, second| {
+                    first.iter_mut().zip(second).for_each(|(x, y)| *x += y);
+                    first
+                },
+            ),
+    }
+}
+
+// Per-atom accumulators used when pairs are only visited once
+struct PairContributions {
+    potential_energies: Vec<f64>,
+    forces: Vec<Vector3<f64>>,
+    virials: Vec<Matrix3<f64>>,
+}
+
+impl PairContributions {
+    fn zeros(atoms_count: usize) -> PairContributions {
+        PairContributions {
+            potential_energies: vec![0.0; atoms_count],
+            forces: vec![Vector3::zeros(); atoms_count],
+            virials: vec![Matrix3::zeros(); atoms_count],
+        }
+    }
+    fn add(
+        &mut self,
+        index: usize,
+        potential_energy: f64,
+        force: Vector3<f64>,
+        virial: Matrix3<f64>,
+    ) {
+        self.potential_energies[index] += potential_energy;
+        self.forces[index] += force;
+        self.virials[index] += virial;
+    }
+    fn merge(mut self, other: PairContributions) -> PairContributions {
+        for index in 0..self.forces.len() {
+            self.potential_energies[index] += other.potential_energies[index];
+            self.forces[index] += other.forces[index];
+            self.virials[index] += other.virials[index];
+        }
+        self
+    }
+}
+
+// Untruncated pair interaction, the truncation scheme being applied on top of it
 pub trait CalculatePotential {
     fn calculate_potential(&self, distance: f64) -> f64;
     fn calculate_force(&self, distance: f64) -> f64;
+    fn cutoff(&self) -> f64;
+    // Integrals of r^2 V(r) and r^3 dV/dr from the cutoff to infinity, used by tail corrections
+    fn tail_integrals(&self) -> Option<(f64, f64)> {
+        None
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use yaml_rust::YamlLoader;
+
+    use super::*;
+
+    pub fn system(definition: &str) -> SystemDefinition {
+        SystemDefinition::from(&YamlLoader::load_from_str(definition).unwrap()[0])
+    }
+
+    // Total energy, forces and virial after evaluating a model from zero over a fresh
+    // neighbors list
+    pub fn evaluate(
+        system: &mut SystemDefinition,
+        cutoff: f64,
+        half: bool,
+        model: &dyn Fn(&mut SystemDefinition, &NeighborsList),
+    ) -> (f64, Vec<Vector3<f64>>, Matrix3<f64>) {
+        let settings = YamlLoader::load_from_str(&format!(
+            "{{cutoff: {:.6}, log: false, method: brute_force, half: {}}}",
+            cutoff, half
+        ))
+        .unwrap();
+        let mut neighbors_list = NeighborsList::from(&settings[0]);
+        neighbors_list.update(system);
+        system.atoms.iter_mut().for_each(|atom| {
+            atom.current.potential_energy = 0.0;
+            atom.current.force = Vector3::zeros();
+            atom.current.virial = Matrix3::zeros();
+        });
+        model(system, &neighbors_list);
+        (
+            system
+                .atoms
+                .iter()
+                .map(|atom| atom.current.potential_energy)
+                .sum(),
+            system.atoms.iter().map(|atom| atom.current.force).collect(),
+            system.atoms.iter().map(|atom| atom.current.virial).sum(),
+        )
+    }
+
+    // Compares every force component with -dE/dx from central differences of the energy
+    pub fn check_numerical_forces(
+        system: &mut SystemDefinition,
+        cutoff: f64,
+        half: bool,
+        model: &dyn Fn(&mut SystemDefinition, &NeighborsList),
+    ) {
+        let step = 1e-5;
+        let (_, forces, _) = evaluate(system, cutoff, half, model);
+        for (index, force) in forces.iter().enumerate() {
+            for k in 0..3 {
+                let mut displaced_energy = |displacement: f64| {
+                    system.atoms[index].current.position[k] += displacement;
+                    let energy = evaluate(system, cutoff, half, model).0;
+                    system.atoms[index].current.position[k] -= displacement;
+                    energy
+                };
+                let numerical_force =
+                    -(displaced_energy(step) - displaced_energy(-step)) / (2.0 * step);
+                assert!(
+                    (force[k] - numerical_force).abs() < 1e-6 * force.norm().max(1.0),
+                    "force {} instead of {} on atom {} along {}",
+                    force[k],
+                    numerical_force,
+                    index,
+                    k
+                );
+            }
+        }
+    }
 }
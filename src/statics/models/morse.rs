@@ -0,0 +1,136 @@
+use crate::io::input::to_f64;
+use crate::statics::models::pair_table::{MixingRule, PairTable};
+use crate::statics::models::truncation::{exponential_tail, parse_tail_correction, Truncation};
+use crate::statics::models::CalculatePotential;
+use yaml_rust::Yaml;
+
+// V(r) = D (exp(-2 alpha (r - r0)) - 2 exp(-alpha (r - r0)))
+#[derive(Clone)]
+pub struct MorsePair {
+    depth: f64,
+    alpha: f64,
+    equilibrium_distance: f64,
+    cutoff: f64,
+}
+
+impl MorsePair {
+    fn from(definition: &Yaml, default_cutoff: Option<f64>) -> MorsePair {
+        MorsePair {
+            depth: to_f64(&definition["depth"]),
+            alpha: to_f64(&definition["alpha"]),
+            equilibrium_distance: to_f64(&definition["r0"]),
+            cutoff: match &definition["cutoff"] {
+                Yaml::BadValue => {
+                    default_cutoff.unwrap_or_else(|| panic!("Morse potential requires a cutoff"))
+                }
+                cutoff => to_f64(cutoff),
+            },
+        }
+    }
+}
+
+pub struct MorseModel {
+    pub pairs: PairTable<MorsePair>,
+    pub truncation: Truncation,
+    pub tail_correction: bool,
+    mixing: MixingRule,
+}
+
+impl MorseModel {
+    pub fn initialize(definition: &Yaml) -> MorseModel {
+        let default_cutoff = match &definition["cutoff"] {
+            Yaml::BadValue => None,
+            cutoff => Some(to_f64(cutoff)),
+        };
+        let mixing = MixingRule::from(&definition["mixing"]);
+        let model = MorseModel {
+            pairs: PairTable::from(
+                definition,
+                &|yaml| MorsePair::from(yaml, default_cutoff),
+                Some(&|first: &MorsePair, second: &MorsePair| MorsePair {
+                    depth: mixing.mix_energy(first.depth, second.depth),
+                    // alpha is an inverse length, mixed through the matching length
+                    alpha: 1.0 / mixing.mix_length(1.0 / first.alpha, 1.0 / second.alpha),
+                    equilibrium_distance: mixing
+                        .mix_length(first.equilibrium_distance, second.equilibrium_distance),
+                    cutoff: default_cutoff.unwrap_or(first.cutoff.max(second.cutoff)),
+                }),
+            ),
+            truncation: Truncation::from(definition),
+            tail_correction: parse_tail_correction(definition),
+            mixing,
+        };
+        model
+            .pairs
+            .values()
+            .for_each(|pair| model.truncation.check_cutoff(pair.cutoff));
+        model
+    }
+    pub fn max_cutoff(&self) -> f64 {
+        self.pairs.values().map(|x| x.cutoff).fold(0.0, f64::max)
+    }
+}
+
+impl std::fmt::Display for MorseModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Morse with {} mixing and {}",
+            self.mixing, self.truncation
+        )
+    }
+}
+
+impl CalculatePotential for MorsePair {
+    fn calculate_potential(&self, distance: f64) -> f64 {
+        let exponential = (-self.alpha * (distance - self.equilibrium_distance)).exp();
+        self.depth * (exponential.powi(2) - 2.0 * exponential)
+    }
+    fn calculate_force(&self, distance: f64) -> f64 {
+        // F = -dV/dr = 2 alpha D (exp(-2 alpha (r - r0)) - exp(-alpha (r - r0)))
+        let exponential = (-self.alpha * (distance - self.equilibrium_distance)).exp();
+        2.0 * self.alpha * self.depth * (exponential.powi(2) - exponential)
+    }
+    fn cutoff(&self) -> f64 {
+        self.cutoff
+    }
+    fn tail_integrals(&self) -> Option<(f64, f64)> {
+        let exponential = (-self.alpha * (self.cutoff - self.equilibrium_distance)).exp();
+        let (alpha, rc) = (self.alpha, self.cutoff);
+        Some((
+            self.depth
+                * (exponential.powi(2) * exponential_tail(2, 2.0 * alpha, rc)
+                    - 2.0 * exponential * exponential_tail(2, alpha, rc)),
+            2.0 * alpha
+                * self.depth
+                * (exponential * exponential_tail(3, alpha, rc)
+                    - exponential.powi(2) * exponential_tail(3, 2.0 * alpha, rc)),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use yaml_rust::YamlLoader;
+
+    use super::*;
+    use crate::statics::models::truncation::tests::{check_forces, truncation_settings};
+
+    #[test]
+    fn forces_match_energy_derivative() {
+        for truncation in truncation_settings(6.5) {
+            let definition = YamlLoader::load_from_str(&format!(
+                "{{model: morse, cutoff: 8.0, {}, parameters: {{depth: 0.3, alpha: 1.4, r0: 2.6}}}}",
+                truncation
+            ))
+            .unwrap();
+            let mut model = MorseModel::initialize(&definition[0]);
+            model.pairs.index_species(&["Ar".to_string()]);
+            check_forces(
+                &model.truncation,
+                model.pairs.get_indexed(0, 0),
+                &[1.8, 2.6, 3.5, 5.0, 6.4, 6.6, 7.5, 7.99, 7.9999],
+            );
+        }
+    }
+}
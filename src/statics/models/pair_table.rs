@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+
+use yaml_rust::Yaml;
+
+// Combination rules giving unlike-pair coefficients from the like-pair ones
+#[derive(Clone, Copy)]
+pub enum MixingRule {
+    LorentzBerthelot, // Geometric mean of energies, arithmetic mean of lengths
+    Geometric,        // Geometric mean of both energies and lengths
+}
+
+impl MixingRule {
+    pub fn from(yaml: &Yaml) -> MixingRule {
+        match yaml.as_str() {
+            Some("lorentz_berthelot") | Some("arithmetic") | None => MixingRule::LorentzBerthelot,
+            Some("geometric") => MixingRule::Geometric,
+            _ => panic!("Unknown mixing rule"),
+        }
+    }
+    pub fn mix_energy(&self, first: f64, second: f64) -> f64 {
+        (first * second).sqrt()
+    }
+    pub fn mix_length(&self, first: f64, second: f64) -> f64 {
+        match self {
+            MixingRule::LorentzBerthelot => 0.5 * (first + second),
+            MixingRule::Geometric => (first * second).sqrt(),
+        }
+    }
+}
+
+impl std::fmt::Display for MixingRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MixingRule::LorentzBerthelot => write!(f, "Lorentz-Berthelot"),
+            MixingRule::Geometric => write!(f, "geometric"),
+        }
+    }
+}
+
+// Combines the coefficients of two like pairs into the ones of the unlike pair
+type MixingFunction<'a, T> = dyn Fn(&T, &T) -> T + 'a;
+
+// Coefficients of a pair model for every couple of species, read from the potential definition:
+//   parameters: coefficients shared by every pair not listed otherwise
+//   species: like-pair coefficients for each species, mixed for unlike pairs when possible
+//   pairs: list of entries with `species: [A, B]` and explicit coefficients, taking precedence
+pub struct PairTable<T> {
+    pairs: HashMap<String, HashMap<String, T>>,
+    default: Option<T>,
+    // Coefficients by species index, set once the species of the system are known
+    species: Vec<String>,
+    indexed: Vec<Vec<Option<T>>>,
+}
+
+impl<T: Clone> PairTable<T> {
+    pub fn from(
+        definition: &Yaml,
+        parse: &dyn Fn(&Yaml) -> T,
+        mix: Option<&MixingFunction<'_, T>>,
+    ) -> PairTable<T> {
+        let mut table = PairTable {
+            pairs: HashMap::new(),
+            default: match &definition["parameters"] {
+                Yaml::BadValue => None,
+                parameters => Some(parse(parameters)),
+            },
+            species: Vec::new(),
+            indexed: Vec::new(),
+        };
+        if let Some(species) = definition["species"].as_hash() {
+            let like_pairs = species
+                .iter()
+                .map(|(name, parameters)| match name.as_str() {
+                    Some(name) => (name.to_string(), parse(parameters)),
+                    None => panic!("Species names must be strings"),
+                })
+                .collect::<Vec<(String, T)>>();
+            for (first_name, first) in like_pairs.iter() {
+                for (second_name, second) in like_pairs.iter() {
+                    match (first_name == second_name, mix) {
+                        (true, _) => table.insert(first_name, second_name, first.clone()),
+                        (false, Some(mix)) => {
+                            table.insert(first_name, second_name, mix(first, second))
+                        }
+                        (false, None) => (),
+                    }
+                }
+            }
+        }
+        if let Some(pairs) = definition["pairs"].as_vec() {
+            for pair in pairs {
+                let names = match pair["species"].as_vec() {
+                    Some(names) if names.len() == 2 => names
+                        .iter()
+                        .map(|name| match name.as_str() {
+                            Some(name) => name.to_string(),
+                            None => panic!("Species names must be strings"),
+                        })
+                        .collect::<Vec<String>>(),
+                    _ => panic!("Every pair must list exactly two species"),
+                };
+                table.insert(&names[0], &names[1], parse(pair));
+            }
+        }
+        if table.default.is_none() && table.pairs.is_empty() {
+            panic!("No pair coefficients defined for the potential");
+        }
+        table
+    }
+    fn insert(&mut self, first: &str, second: &str, coefficients: T) {
+        for (a, b) in [(first, second), (second, first)] {
+            self.pairs
+                .entry(a.to_string())
+                .or_default()
+                .insert(b.to_string(), coefficients.clone());
+        }
+    }
+    fn find(&self, first: &str, second: &str) -> Option<&T> {
+        self.pairs
+            .get(first)
+            .and_then(|x| x.get(second))
+            .or(self.default.as_ref())
+    }
+    // Looks up the coefficients of every couple of species once, so that pairs are then
+    // found by species index
+    pub fn index_species(&mut self, species: &[String]) {
+        self.indexed = species
+            .iter()
+            .map(|first| {
+                species
+                    .iter()
+                    .map(|second| self.find(first, second).cloned())
+                    .collect()
+            })
+            .collect();
+        self.species = species.to_vec();
+    }
+    // Coefficients of a pair of species indices, as given to index_species
+    pub fn get_indexed(&self, first: usize, second: usize) -> &T {
+        match &self.indexed[first][second] {
+            Some(coefficients) => coefficients,
+            None => panic!(
+                "No pair coefficients defined for {}-{}",
+                self.species[first], self.species[second]
+            ),
+        }
+    }
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.pairs
+            .values()
+            .flat_map(|x| x.values())
+            .chain(self.default.iter())
+    }
+}
+
+// Species pairs a term of a composite potential is restricted to, read from
+// `species_pairs: [[A, B], ...]`. Every pair is selected when the list is missing.
+pub struct SpeciesSelection {
+    pairs: Option<HashMap<String, Vec<String>>>,
+    // Selected pairs and species by species index, set once the species of the system are known
+    selected_pairs: Vec<Vec<bool>>,
+    selected_species: Vec<bool>,
+}
+
+impl SpeciesSelection {
+    pub fn from(yaml: &Yaml) -> SpeciesSelection {
+        let pairs = match yaml {
+            Yaml::BadValue => {
+                return SpeciesSelection {
+                    pairs: None,
+                    selected_pairs: Vec::new(),
+                    selected_species: Vec::new(),
+                }
+            }
+            Yaml::Array(pairs) => pairs,
+            _ => panic!("species_pairs must be a list of species pairs"),
+        };
+        let mut selection: HashMap<String, Vec<String>> = HashMap::new();
+        for pair in pairs {
+            let names = match pair.as_vec() {
+                Some(names) if names.len() == 2 => names
+                    .iter()
+                    .map(|name| match name.as_str() {
+                        Some(name) => name.to_string(),
+                        None => panic!("Species names must be strings"),
+                    })
+                    .collect::<Vec<String>>(),
+                _ => panic!("Every entry of species_pairs must list exactly two species"),
+            };
+            selection
+                .entry(names[0].clone())
+                .or_default()
+                .push(names[1].clone());
+            selection
+                .entry(names[1].clone())
+                .or_default()
+                .push(names[0].clone());
+        }
+        SpeciesSelection {
+            pairs: Some(selection),
+            selected_pairs: Vec::new(),
+            selected_species: Vec::new(),
+        }
+    }
+    pub fn is_complete(&self) -> bool {
+        self.pairs.is_none()
+    }
+    pub fn index_species(&mut self, species: &[String]) {
+        let pairs = match &self.pairs {
+            Some(pairs) => pairs,
+            None => return,
+        };
+        self.selected_pairs = species
+            .iter()
+            .map(|first| {
+                species
+                    .iter()
+                    .map(|second| {
+                        pairs
+                            .get(first)
+                            .is_some_and(|others| others.contains(second))
+                    })
+                    .collect()
+            })
+            .collect();
+        self.selected_species = species.iter().map(|x| pairs.contains_key(x)).collect();
+    }
+    // Whether a pair of species indices, as given to index_species, is selected
+    pub fn includes_pair(&self, first: usize, second: usize) -> bool {
+        match &self.pairs {
+            Some(_) => self.selected_pairs[first][second],
+            None => true,
+        }
+    }
+    // Whether the species takes part in at least one selected pair
+    pub fn includes_species(&self, species: usize) -> bool {
+        match &self.pairs {
+            Some(_) => self.selected_species[species],
+            None => true,
+        }
+    }
+}
+
+impl std::fmt::Display for SpeciesSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.pairs {
+            Some(pairs) => {
+                let mut names = pairs
+                    .iter()
+                    .flat_map(|(first, others)| {
+                        others
+                            .iter()
+                            .filter(move |second| first <= *second)
+                            .map(move |second| format!("{}-{}", first, second))
+                    })
+                    .collect::<Vec<String>>();
+                names.sort();
+                names.dedup();
+                write!(f, "{}", names.join(", "))
+            }
+            None => write!(f, "all pairs"),
+        }
+    }
+}
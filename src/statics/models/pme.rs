@@ -0,0 +1,593 @@
+use std::f64::consts::PI;
+use std::sync::Mutex;
+
+use nalgebra::{Matrix3, Vector3};
+use rayon::prelude::*;
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use yaml_rust::Yaml;
+
+use crate::dynamics::neighbors::NeighborsList;
+use crate::io::input::to_f64;
+use crate::statics::models::bonded::SpecialFactors;
+use crate::statics::models::ewald::{
+    absolute_accuracy, add_direct_terms, splitting_parameter, squared_charges,
+};
+use crate::system::SystemDefinition;
+
+const MAX_ORDER: usize = 7;
+
+// Coefficients of the reciprocal force error estimate for each interpolation order
+// (Deserno and Holm, J. Chem. Phys. 109, 7694), as used by LAMMPS
+const ERROR_COEFFICIENTS: [&[f64]; MAX_ORDER + 1] = [
+    &[],
+    &[2.0 / 3.0],
+    &[1.0 / 50.0, 5.0 / 294.0],
+    &[1.0 / 588.0, 7.0 / 1440.0, 21.0 / 3872.0],
+    &[
+        1.0 / 4320.0,
+        3.0 / 1936.0,
+        7601.0 / 2271360.0,
+        143.0 / 28800.0,
+    ],
+    &[
+        1.0 / 23232.0,
+        7601.0 / 13628160.0,
+        143.0 / 69120.0,
+        517231.0 / 106536960.0,
+        106640677.0 / 11737571328.0,
+    ],
+    &[
+        691.0 / 68140800.0,
+        13.0 / 57600.0,
+        47021.0 / 35512320.0,
+        9694607.0 / 2095994880.0,
+        733191589.0 / 59609088000.0,
+        326190917.0 / 11700633600.0,
+    ],
+    &[
+        1.0 / 345600.0,
+        3617.0 / 35512320.0,
+        745739.0 / 838397952.0,
+        56399353.0 / 12773376000.0,
+        25091609.0 / 1560084480.0,
+        1755948832039.0 / 36229939200000.0,
+        4887769399.0 / 37838389248.0,
+    ],
+];
+
+// Smooth particle-mesh Ewald (Essmann et al., J. Chem. Phys. 103, 8577): charges are spread
+// on a grid with cardinal B-splines and the reciprocal sum is done with FFTs
+pub struct ParticleMesh {
+    pub cutoff: f64,
+    accuracy: f64,
+    // Splitting parameter, grid points along each box vector and B-spline order,
+    // chosen from the accuracy when missing
+    alpha: Option<f64>,
+    grid: Option<[usize; 3]>,
+    order: Option<usize>,
+    reciprocal_grid: Mutex<Option<ReciprocalGrid>>,
+}
+
+// Parameters fixed at the first evaluation, with the influence function of the last box seen
+struct ReciprocalGrid {
+    alpha: f64,
+    grid: [usize; 3],
+    order: usize,
+    moduli: [Vec<f64>; 3],
+    box_vectors: Matrix3<f64>,
+    // Influence function G(m) and reciprocal vector m of every grid point
+    influence: Vec<(f64, Vector3<f64>)>,
+}
+
+impl ParticleMesh {
+    pub fn from(definition: &Yaml) -> ParticleMesh {
+        ParticleMesh {
+            cutoff: match &definition["cutoff"] {
+                Yaml::BadValue => panic!("Particle-mesh Ewald requires a real-space cutoff"),
+                cutoff => to_f64(cutoff),
+            },
+            accuracy: match &definition["accuracy"] {
+                Yaml::BadValue => 1e-5,
+                accuracy => to_f64(accuracy),
+            },
+            alpha: match &definition["alpha"] {
+                Yaml::BadValue => None,
+                alpha => Some(to_f64(alpha)),
+            },
+            grid: match definition["grid"].as_vec() {
+                Some(grid) if grid.len() == 3 => Some([0, 1, 2].map(|i| match grid[i].as_i64() {
+                    Some(x) if x > 0 => x as usize,
+                    _ => panic!("Mesh grid must be three positive integers"),
+                })),
+                Some(_) => panic!("Mesh grid must be three positive integers"),
+                None => None,
+            },
+            order: match definition["order"].as_i64() {
+                Some(order) if (3..=MAX_ORDER as i64).contains(&order) => Some(order as usize),
+                Some(_) => panic!("B-spline order must lie between 3 and {}", MAX_ORDER),
+                None => None,
+            },
+            reciprocal_grid: Mutex::new(None),
+        }
+    }
+    // Splitting parameter, grid and order. Without a given order, every order is tried and
+    // the cheapest one is kept, counting the FFTs and the charge spreading.
+    fn parameters(
+        &self,
+        system: &SystemDefinition,
+        squared_charges: f64,
+    ) -> (f64, [usize; 3], usize) {
+        let atoms_count = system.atoms.len() as f64;
+        let widths = system.simulation_box.perpendicular_widths();
+        let q2 = system.units.coulomb_constant() * squared_charges;
+        let accuracy = absolute_accuracy(&system.units, self.accuracy);
+        let alpha = match self.alpha {
+            Some(alpha) => alpha,
+            None => splitting_parameter(system, accuracy, q2, self.cutoff),
+        };
+        let orders = match self.order {
+            Some(order) => order..=order,
+            None => 3..=MAX_ORDER,
+        };
+        let (grid, order, _) = orders
+            .map(|order| {
+                let grid = match self.grid {
+                    Some(grid) => grid,
+                    None => [0, 1, 2].map(|i| {
+                        let error = |points: usize| {
+                            let h = widths[i] / points as f64;
+                            let sum = ERROR_COEFFICIENTS[order]
+                                .iter()
+                                .enumerate()
+                                .map(|(m, a)| a * (h * alpha).powi(2 * m as i32))
+                                .sum::<f64>();
+                            q2 * (h * alpha).powi(order as i32)
+                                * (alpha * widths[i] * (2.0 * PI).sqrt() * sum / atoms_count).sqrt()
+                                / widths[i].powi(2)
+                        };
+                        let mut points = order.max(2);
+                        while !is_fft_friendly(points) || error(points) > accuracy {
+                            points += 1;
+                        }
+                        points
+                    }),
+                };
+                let grid_points = grid.iter().product::<usize>() as f64;
+                let cost = grid_points * grid_points.log2() + atoms_count * (order as f64).powi(3);
+                (grid, order, cost)
+            })
+            .fold(([0; 3], 0, f64::INFINITY), |best, candidate| {
+                match candidate.2 < best.2 {
+                    true => candidate,
+                    false => best,
+                }
+            });
+        if grid.iter().any(|points| *points < order) {
+            panic!("Mesh grid must have at least as many points as the B-spline order");
+        }
+        (alpha, grid, order)
+    }
+    // Parameters are chosen at the first evaluation, the influence function being rebuilt
+    // on the same grid whenever the box changes
+    fn reciprocal_grid<'a>(
+        &self,
+        reciprocal_grid: &'a mut Option<ReciprocalGrid>,
+        system: &SystemDefinition,
+        squared_charges: f64,
+    ) -> &'a ReciprocalGrid {
+        let reciprocal_grid = reciprocal_grid.get_or_insert_with(|| {
+            let (alpha, grid, order) = self.parameters(system, squared_charges);
+            ReciprocalGrid {
+                alpha,
+                grid,
+                order,
+                moduli: [0, 1, 2].map(|i| spline_moduli(grid[i], order)),
+                box_vectors: Matrix3::zeros(),
+                influence: Vec::new(),
+            }
+        });
+        if reciprocal_grid.box_vectors != system.simulation_box.vectors {
+            reciprocal_grid.box_vectors = system.simulation_box.vectors;
+            reciprocal_grid.influence = influence_function(system, reciprocal_grid);
+        }
+        reciprocal_grid
+    }
+    pub fn evaluate(
+        &self,
+        system: &mut SystemDefinition,
+        neighbors_list: &NeighborsList,
+        special_factors: &SpecialFactors,
+    ) {
+        let squared_charges = squared_charges(system);
+        if squared_charges == 0.0 {
+            return;
+        }
+        let mut reciprocal_grid = self.reciprocal_grid.lock().unwrap();
+        let reciprocal_grid = self.reciprocal_grid(&mut reciprocal_grid, system, squared_charges);
+        let (alpha, grid, order) = (
+            reciprocal_grid.alpha,
+            reciprocal_grid.grid,
+            reciprocal_grid.order,
+        );
+        add_direct_terms(system, neighbors_list, alpha, self.cutoff, special_factors);
+
+        let fractional_matrix = system.simulation_box.fractional_matrix;
+        let splines = system
+            .atoms
+            .par_iter()
+            .map(|atom| AtomSpline::new(&fractional_matrix, &atom.current.position, &grid, order))
+            .collect::<Vec<AtomSpline>>();
+
+        // Charges spread on the grid, each thread filling its own copy
+        let points_count = grid.iter().product::<usize>();
+        let charges = system
+            .atoms
+            .par_iter()
+            .zip(splines.par_iter())
+            .fold(
+                || vec![0.0; points_count],
+                |mut charges, (atom, spline)| {
+                    spline.for_each_point(&grid, order, |index, weight, _| {
+                        charges[index] += atom.charge * weight
+                    });
+                    charges
+                },
+            )
+            .reduce(
+                || vec![0.0; points_count],
+                |mut first, second| {
+                    first.iter_mut().zip(second).for_each(|(x, y)| *x += y);
+                    first
+                },
+            );
+
+        // Reciprocal sum E = 1/2 sum_m G(m) |F(Q)(m)|^2
+        let mut planner = FftPlanner::new();
+        let mut transformed = charges
+            .iter()
+            .map(|x| Complex::new(*x, 0.0))
+            .collect::<Vec<Complex<f64>>>();
+        transform(&mut transformed, &grid, false, &mut planner);
+        let (_, virial) = transformed
+            .par_iter_mut()
+            .zip(reciprocal_grid.influence.par_iter())
+            .map(|(value, (influence, m))| {
+                let m2 = m.norm_squared();
+                if m2 == 0.0 {
+                    *value = Complex::new(0.0, 0.0);
+                    return (0.0, Matrix3::zeros());
+                }
+                let energy = 0.5 * influence * value.norm_sqr();
+                *value *= influence;
+                let virial = energy
+                    * (Matrix3::identity()
+                        - 2.0 * (1.0 + PI.powi(2) * m2 / alpha.powi(2)) / m2 * m * m.transpose());
+                (energy, virial)
+            })
+            .reduce(
+                || (0.0, Matrix3::zeros()),
+                |first, second| (first.0 + second.0, first.1 + second.1),
+            );
+        // Potential on the grid points, the convolution of the charges with G
+        transform(&mut transformed, &grid, true, &mut planner);
+
+        // The reciprocal virial is shared evenly, the per-atom split not being unique
+        let atom_virial = virial / system.atoms.len() as f64;
+        let scaling = Vector3::from_fn(|i, _| grid[i] as f64);
+        system
+            .atoms
+            .par_iter_mut()
+            .zip(splines.par_iter())
+            .for_each(|(atom, spline)| {
+                let mut potential = 0.0;
+                let mut gradient = Vector3::zeros();
+                spline.for_each_point(&grid, order, |index, weight, derivative| {
+                    potential += weight * transformed[index].re;
+                    gradient += derivative * transformed[index].re;
+                });
+                atom.current.potential_energy += 0.5 * atom.charge * potential;
+                // Gradient in grid units mapped back to cartesian coordinates
+                atom.current.force -=
+                    atom.charge * fractional_matrix.transpose() * gradient.component_mul(&scaling);
+                atom.current.virial += atom_virial;
+            });
+    }
+}
+
+impl std::fmt::Display for ParticleMesh {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Particle-mesh Ewald with real-space cutoff {} and accuracy {}",
+            self.cutoff, self.accuracy
+        )
+    }
+}
+
+// Influence function
+//   G(m) = exp(-pi^2 m^2 / alpha^2) / (pi V m^2) B(m)
+// for reciprocal vectors m = F^T n, F mapping cartesian to fractional coordinates
+fn influence_function(
+    system: &SystemDefinition,
+    reciprocal_grid: &ReciprocalGrid,
+) -> Vec<(f64, Vector3<f64>)> {
+    let (alpha, grid, moduli) = (
+        reciprocal_grid.alpha,
+        reciprocal_grid.grid,
+        &reciprocal_grid.moduli,
+    );
+    let coulomb_constant = system.units.coulomb_constant();
+    let volume = system.simulation_box.volume();
+    let fractional_matrix = system.simulation_box.fractional_matrix;
+    (0..grid.iter().product::<usize>())
+        .into_par_iter()
+        .map(|index| {
+            let n = [
+                index / (grid[1] * grid[2]),
+                (index / grid[2]) % grid[1],
+                index % grid[2],
+            ];
+            if n == [0, 0, 0] {
+                return (0.0, Vector3::zeros());
+            }
+            let frequencies = Vector3::from_fn(|i, _| match n[i] <= grid[i] / 2 {
+                true => n[i] as f64,
+                false => n[i] as f64 - grid[i] as f64,
+            });
+            let m = fractional_matrix.transpose() * frequencies;
+            let m2 = m.norm_squared();
+            let influence = coulomb_constant * (-(PI.powi(2) * m2 / alpha.powi(2))).exp()
+                / (PI * volume * m2)
+                * moduli[0][n[0]]
+                * moduli[1][n[1]]
+                * moduli[2][n[2]];
+            (influence, m)
+        })
+        .collect()
+}
+
+// B-spline weights of one atom along each grid axis. The weight of point base - j is
+// M_n(w + j), w being the fractional part of the scaled coordinate.
+struct AtomSpline {
+    base: [usize; 3],
+    weights: [[f64; MAX_ORDER]; 3],
+    derivatives: [[f64; MAX_ORDER]; 3],
+}
+
+impl AtomSpline {
+    fn new(
+        fractional_matrix: &Matrix3<f64>,
+        position: &Vector3<f64>,
+        grid: &[usize; 3],
+        order: usize,
+    ) -> AtomSpline {
+        let fractional = fractional_matrix * position;
+        let mut spline = AtomSpline {
+            base: [0; 3],
+            weights: [[0.0; MAX_ORDER]; 3],
+            derivatives: [[0.0; MAX_ORDER]; 3],
+        };
+        for i in 0..3 {
+            let scaled = fractional[i] * grid[i] as f64;
+            let base = scaled.floor();
+            // Wrapping the grid index rather than the coordinate keeps w consistent with it
+            spline.base[i] = (base as i64).rem_euclid(grid[i] as i64) as usize;
+            (spline.weights[i], spline.derivatives[i]) = bspline(order, scaled - base);
+        }
+        spline
+    }
+    // Calls f(index, weight, weight gradient in grid units) for every grid point the atom reaches
+    fn for_each_point(
+        &self,
+        grid: &[usize; 3],
+        order: usize,
+        mut f: impl FnMut(usize, f64, Vector3<f64>),
+    ) {
+        let wrap = |i: usize, j: usize| (self.base[i] + grid[i] * order - j) % grid[i];
+        for a in 0..order {
+            let x = wrap(0, a);
+            for b in 0..order {
+                let y = wrap(1, b);
+                let weight_xy = self.weights[0][a] * self.weights[1][b];
+                for c in 0..order {
+                    let index = (x * grid[1] + y) * grid[2] + wrap(2, c);
+                    let (wx, wy, wz) = (self.weights[0][a], self.weights[1][b], self.weights[2][c]);
+                    let gradient = Vector3::new(
+                        self.derivatives[0][a] * wy * wz,
+                        wx * self.derivatives[1][b] * wz,
+                        weight_xy * self.derivatives[2][c],
+                    );
+                    f(index, weight_xy * wz, gradient);
+                }
+            }
+        }
+    }
+}
+
+// Cardinal B-spline M_n(w + j) and its derivative for j = 0..n-1, built up from
+//   M_k(x) = (x M_k-1(x) + (k - x) M_k-1(x - 1)) / (k - 1)
+fn bspline(order: usize, w: f64) -> ([f64; MAX_ORDER], [f64; MAX_ORDER]) {
+    let mut values = [0.0; MAX_ORDER];
+    let mut derivatives = [0.0; MAX_ORDER];
+    values[0] = 1.0;
+    for k in 2..=order {
+        if k == order {
+            // M_n'(x) = M_n-1(x) - M_n-1(x - 1)
+            for j in 0..order {
+                let previous = if j > 0 { values[j - 1] } else { 0.0 };
+                derivatives[j] = values[j] - previous;
+            }
+        }
+        for j in (0..k).rev() {
+            let x = w + j as f64;
+            let current = if j < k - 1 { values[j] } else { 0.0 };
+            let previous = if j > 0 { values[j - 1] } else { 0.0 };
+            values[j] = (x * current + (k as f64 - x) * previous) / (k - 1) as f64;
+        }
+    }
+    (values, derivatives)
+}
+
+// |b(m)|^2 = 1 / |sum_k M_n(k + 1) exp(2 pi i m k / K)|^2 for every m of a grid axis
+fn spline_moduli(points: usize, order: usize) -> Vec<f64> {
+    let (values, _) = bspline(order, 0.0);
+    let mut moduli = (0..points)
+        .map(|m| {
+            let denominator = (0..order - 1).fold(Complex::new(0.0, 0.0), |sum, k| {
+                let phase = 2.0 * PI * (m * k) as f64 / points as f64;
+                sum + values[k + 1] * Complex::new(phase.cos(), phase.sin())
+            });
+            denominator.norm_sqr()
+        })
+        .collect::<Vec<f64>>();
+    // Odd orders vanish at the Nyquist frequency, where neighbors are averaged instead
+    for m in 0..points {
+        if moduli[m] < 1e-7 {
+            moduli[m] = 0.5 * (moduli[(m + points - 1) % points] + moduli[(m + 1) % points]);
+        }
+    }
+    moduli.iter().map(|x| 1.0 / x).collect()
+}
+
+// Sizes whose only prime factors are 2, 3 and 5, which transform fastest
+fn is_fft_friendly(mut points: usize) -> bool {
+    for factor in [2, 3, 5] {
+        while points.is_multiple_of(factor) {
+            points /= factor;
+        }
+    }
+    points == 1
+}
+
+// Unnormalized 3D FFT, done as 1D transforms along each axis with lines spread over threads
+fn transform(
+    data: &mut [Complex<f64>],
+    grid: &[usize; 3],
+    inverse: bool,
+    planner: &mut FftPlanner<f64>,
+) {
+    for axis in 0..3 {
+        let length = grid[axis];
+        let fft = match inverse {
+            true => planner.plan_fft_inverse(length),
+            false => planner.plan_fft_forward(length),
+        };
+        let stride = grid[axis + 1..].iter().product::<usize>();
+        if stride == 1 {
+            data.par_chunks_mut(length)
+                .for_each(|line| fft.process(line));
+            continue;
+        }
+        let lines = (0..data.len() / length)
+            .into_par_iter()
+            .map(|line| {
+                let start = (line / stride) * length * stride + line % stride;
+                let mut buffer = (0..length)
+                    .map(|j| data[start + j * stride])
+                    .collect::<Vec<Complex<f64>>>();
+                fft.process(&mut buffer);
+                buffer
+            })
+            .collect::<Vec<Vec<Complex<f64>>>>();
+        data.par_chunks_mut(length * stride)
+            .enumerate()
+            .for_each(|(block_index, block)| {
+                for i in 0..stride {
+                    let line = &lines[block_index * stride + i];
+                    for j in 0..length {
+                        block[j * stride + i] = line[j];
+                    }
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use yaml_rust::YamlLoader;
+
+    use super::*;
+    use crate::statics::models::bonded::UNSCALED;
+    use crate::statics::models::ewald::Ewald;
+    use crate::statics::models::tests::{evaluate, system};
+
+    #[test]
+    fn bspline_is_a_partition_of_unity() {
+        for order in 3..=MAX_ORDER {
+            for w in [0.0, 0.1, 0.37, 0.5, 0.82, 0.999] {
+                let (values, derivatives) = bspline(order, w);
+                assert!((values.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+                assert!(derivatives.iter().sum::<f64>().abs() < 1e-12);
+                assert!(values[order..].iter().all(|x| *x == 0.0));
+                // Derivatives against central differences of the weights
+                let (after, _) = bspline(order, w + 1e-6);
+                let (before, _) = bspline(order, w - 1e-6);
+                for j in 0..order {
+                    let numerical = (after[j] - before[j]) / 2e-6;
+                    assert!((derivatives[j] - numerical).abs() < 1e-6);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn odd_order_moduli_are_averaged_at_nyquist_frequency() {
+        for order in [3, 5, 7] {
+            let moduli = spline_moduli(12, order);
+            assert!((moduli[0] - 1.0).abs() < 1e-12);
+            assert!(moduli.iter().all(|x| x.is_finite() && *x > 0.0));
+            assert!((1.0 / moduli[6] - 0.5 * (1.0 / moduli[5] + 1.0 / moduli[7])).abs() < 1e-12);
+            // Moduli are even in m
+            for m in 1..12 {
+                assert!((moduli[m] - moduli[12 - m]).abs() < 1e-12 * moduli[m]);
+            }
+        }
+        // Even orders are left untouched at the Nyquist frequency
+        let moduli = spline_moduli(12, 4);
+        assert!((1.0 / moduli[6] - 0.5 * (1.0 / moduli[5] + 1.0 / moduli[7])).abs() > 1e-3);
+    }
+
+    #[test]
+    fn mesh_matches_ewald_summation_on_skewed_cell() {
+        let settings = "{cutoff: 4.5, alpha: 0.7, accuracy: 1e-8, kmax: [9, 9, 9], grid: [40, 40, 40], order: 7}";
+        let settings = &YamlLoader::load_from_str(settings).unwrap()[0];
+        let (ewald, mesh) = (Ewald::from(settings), ParticleMesh::from(settings));
+        let mut system = system(
+            "
+            cell: [[10.0, 0, 0], [2.0, 9.5, 0], [-1.5, 1.0, 10.5]]
+            periodicity: xyz
+            units: atomic
+            atoms:
+              - {name: Na, charge: 1.0, position: [0.10, 0.15, 0.20]}
+              - {name: Cl, charge: -0.8, position: [0.35, 0.22, 0.14]}
+              - {name: Na, charge: 0.6, position: [0.62, 0.71, 0.55]}
+              - {name: Cl, charge: -1.2, position: [0.84, 0.40, 0.77]}
+              - {name: Na, charge: 0.3, position: [0.27, 0.88, 0.61]}
+            ",
+        );
+        for deformation in [
+            Matrix3::identity(),
+            Matrix3::new(1.02, 0.03, 0.0, 0.0, 0.98, 0.01, 0.02, 0.0, 1.01),
+        ] {
+            system.simulation_box.deform(&deformation);
+            system
+                .atoms
+                .iter_mut()
+                .for_each(|atom| atom.current.position = deformation * atom.current.position);
+            let (ewald_energy, ewald_forces, ewald_virial) =
+                evaluate(&mut system, 4.5, true, &|system, neighbors_list| {
+                    ewald.evaluate(system, neighbors_list, &UNSCALED)
+                });
+            let (mesh_energy, mesh_forces, mesh_virial) =
+                evaluate(&mut system, 4.5, true, &|system, neighbors_list| {
+                    mesh.evaluate(system, neighbors_list, &UNSCALED)
+                });
+            assert!((mesh_energy - ewald_energy).abs() < 1e-5 * ewald_energy.abs());
+            for (mesh_force, ewald_force) in mesh_forces.iter().zip(ewald_forces.iter()) {
+                assert!((mesh_force - ewald_force).norm() < 1e-4 * ewald_force.norm().max(1.0));
+            }
+            assert!((mesh_virial - ewald_virial).norm() < 1e-5 * ewald_virial.norm());
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use std::fs::read_to_string;
+
+use yaml_rust::Yaml;
+
+use crate::io::input::to_f64;
+use crate::statics::models::pair_table::PairTable;
+use crate::statics::models::truncation::Truncation;
+use crate::statics::models::CalculatePotential;
+use crate::utils::spline::Spline;
+
+pub enum Interpolation {
+    Linear,
+    Spline,
+}
+
+impl Interpolation {
+    fn from(yaml: &Yaml) -> Interpolation {
+        match yaml.as_str() {
+            Some("linear") => Interpolation::Linear,
+            Some("spline") | None => Interpolation::Spline,
+            _ => panic!("Unknown table interpolation"),
+        }
+    }
+    fn interpolate(&self, distances: &[f64], values: Vec<f64>) -> Spline {
+        match self {
+            Interpolation::Linear => Spline::linear(distances.to_vec(), values),
+            Interpolation::Spline => Spline::cubic(distances.to_vec(), values),
+        }
+    }
+}
+
+impl std::fmt::Display for Interpolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Interpolation::Linear => write!(f, "linear"),
+            Interpolation::Spline => write!(f, "cubic spline"),
+        }
+    }
+}
+
+// Pair interaction interpolated from one section of a LAMMPS `pair_style table` file:
+//   KEYWORD
+//   N n [R rlo rhi | RSQ rlo rhi] [FPRIME fplo fphi]
+//   index r energy force   (n lines)
+#[derive(Clone)]
+pub struct TabulatedPair {
+    energy: Spline,
+    force: Option<Spline>, // Forces are the energy derivative when not taken from the file
+    cutoff: f64,
+}
+
+impl TabulatedPair {
+    fn from(
+        definition: &Yaml,
+        interpolation: &Interpolation,
+        derive_forces: bool,
+        default_cutoff: Option<f64>,
+    ) -> TabulatedPair {
+        let filename = match definition["file"].as_str() {
+            Some(x) => x,
+            None => panic!("Tabulated pairs require a file"),
+        };
+        let keyword = match definition["keyword"].as_str() {
+            Some(x) => x,
+            None => panic!("Tabulated pairs require the keyword of their section"),
+        };
+        let content = match read_to_string(filename) {
+            Ok(x) => x,
+            Err(_) => panic!("Failed to read table file {}", filename),
+        };
+        let (distances, energies, forces) = read_section(&content, keyword, filename);
+        let cutoff = match &definition["cutoff"] {
+            Yaml::BadValue => default_cutoff.unwrap_or(distances[distances.len() - 1]),
+            cutoff => to_f64(cutoff),
+        };
+        if cutoff > distances[distances.len() - 1] {
+            panic!(
+                "Cutoff {} lies beyond the end of table {} in {}",
+                cutoff, keyword, filename
+            );
+        }
+        TabulatedPair {
+            energy: interpolation.interpolate(&distances, energies),
+            force: match derive_forces {
+                true => None,
+                false => Some(interpolation.interpolate(&distances, forces)),
+            },
+            cutoff,
+        }
+    }
+    fn check_distance(&self, distance: f64) {
+        if distance < self.energy.start() {
+            panic!(
+                "Pair distance {} lies below the start of its table ({})",
+                distance,
+                self.energy.start()
+            );
+        }
+    }
+}
+
+// Distances, energies and forces of the section named by the keyword
+fn read_section(content: &str, keyword: &str, filename: &str) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+    let mut lines = content
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>())
+        .filter(|tokens| !tokens.is_empty() && !tokens[0].starts_with('#'));
+    let parse = |token: &str| match token.parse::<f64>() {
+        Ok(x) => x,
+        Err(_) => panic!("Invalid number {} in table file {}", token, filename),
+    };
+    while let Some(tokens) = lines.next() {
+        let section = tokens[0];
+        let parameters = match lines.next() {
+            Some(x) => x,
+            None => panic!("Missing parameters of table {} in {}", section, filename),
+        };
+        let mut points_count = None;
+        let mut grid = None;
+        let mut position = 0;
+        while position < parameters.len() {
+            match parameters[position] {
+                "N" => points_count = Some(parse(parameters[position + 1]) as usize),
+                "R" | "RSQ" => {
+                    grid = Some((
+                        parameters[position] == "RSQ",
+                        parse(parameters[position + 1]),
+                        parse(parameters[position + 2]),
+                    ));
+                    position += 1;
+                }
+                "FPRIME" => position += 1,
+                x => panic!("Unsupported table parameter {} in {}", x, filename),
+            }
+            position += 2;
+        }
+        let points_count = match points_count {
+            Some(x) if x >= 2 => x,
+            _ => panic!(
+                "Table {} in {} needs at least two points",
+                section, filename
+            ),
+        };
+        let rows = (0..points_count)
+            .map(|_| match lines.next() {
+                Some(row) if row.len() >= 4 => row,
+                _ => panic!("Incomplete table {} in {}", section, filename),
+            })
+            .collect::<Vec<Vec<&str>>>();
+        if section != keyword {
+            continue;
+        }
+        // R and RSQ override the listed distances with a grid uniform in r or r^2
+        let distances = rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| match grid {
+                None => parse(row[1]),
+                Some((squared, low, high)) => {
+                    let fraction = i as f64 / (points_count - 1) as f64;
+                    match squared {
+                        false => low + (high - low) * fraction,
+                        true => (low.powi(2) + (high.powi(2) - low.powi(2)) * fraction).sqrt(),
+                    }
+                }
+            })
+            .collect();
+        let energies = rows.iter().map(|row| parse(row[2])).collect();
+        let forces = rows.iter().map(|row| parse(row[3])).collect();
+        return (distances, energies, forces);
+    }
+    panic!("No table {} found in {}", keyword, filename)
+}
+
+pub struct TabulatedModel {
+    pub pairs: PairTable<TabulatedPair>,
+    pub truncation: Truncation,
+    interpolation: Interpolation,
+    derive_forces: bool,
+}
+
+impl TabulatedModel {
+    pub fn initialize(definition: &Yaml) -> TabulatedModel {
+        let default_cutoff = match &definition["cutoff"] {
+            Yaml::BadValue => None,
+            cutoff => Some(to_f64(cutoff)),
+        };
+        let interpolation = Interpolation::from(&definition["interpolation"]);
+        let derive_forces = match &definition["derive_forces"] {
+            Yaml::Boolean(x) => *x,
+            Yaml::BadValue => false,
+            _ => panic!("derive_forces must be a boolean"),
+        };
+        if let Yaml::Boolean(true) = definition["tail_correction"] {
+            panic!("Tail corrections are not available for tabulated potentials");
+        }
+        let model = TabulatedModel {
+            pairs: PairTable::from(
+                definition,
+                &|yaml| TabulatedPair::from(yaml, &interpolation, derive_forces, default_cutoff),
+                None,
+            ),
+            truncation: Truncation::from(definition),
+            interpolation,
+            derive_forces,
+        };
+        model
+            .pairs
+            .values()
+            .for_each(|pair| model.truncation.check_cutoff(pair.cutoff));
+        model
+    }
+    pub fn max_cutoff(&self) -> f64 {
+        self.pairs.values().map(|x| x.cutoff).fold(0.0, f64::max)
+    }
+}
+
+impl std::fmt::Display for TabulatedModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Tabulated pairs with {} interpolation, forces {} and {}",
+            self.interpolation,
+            match self.derive_forces {
+                true => "derived from energies",
+                false => "read from tables",
+            },
+            self.truncation
+        )
+    }
+}
+
+impl CalculatePotential for TabulatedPair {
+    fn calculate_potential(&self, distance: f64) -> f64 {
+        self.check_distance(distance);
+        self.energy.evaluate(distance).0
+    }
+    fn calculate_force(&self, distance: f64) -> f64 {
+        self.check_distance(distance);
+        match &self.force {
+            Some(force) => force.evaluate(distance).0,
+            None => -self.energy.evaluate(distance).1,
+        }
+    }
+    fn cutoff(&self) -> f64 {
+        self.cutoff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &str = "
+# DATE: 2024-01-01 UNITS: metal
+# Test tables
+
+FIRST
+N 3
+
+1 1.0 3.0 6.0
+2 1.5 1.0 2.0
+3 2.0 0.0 0.5
+
+UNIFORM
+N 3 R 1.0 2.0
+
+1 9.0 3.0 6.0
+2 9.0 1.0 2.0
+3 9.0 0.0 0.5
+
+SQUARED
+N 3 RSQ 1.0 2.0 FPRIME -6.0 0.0
+
+1 9.0 3.0 6.0
+2 9.0 1.0 2.0
+3 9.0 0.0 0.5
+";
+
+    #[test]
+    fn sections_are_read_by_keyword() {
+        let (distances, energies, forces) = read_section(TABLE, "FIRST", "test.table");
+        assert_eq!(distances, [1.0, 1.5, 2.0]);
+        assert_eq!(energies, [3.0, 1.0, 0.0]);
+        assert_eq!(forces, [6.0, 2.0, 0.5]);
+        // R and RSQ replace the listed distances, FPRIME is skipped
+        let (distances, energies, _) = read_section(TABLE, "UNIFORM", "test.table");
+        assert_eq!(distances, [1.0, 1.5, 2.0]);
+        assert_eq!(energies, [3.0, 1.0, 0.0]);
+        let (distances, _, forces) = read_section(TABLE, "SQUARED", "test.table");
+        assert_eq!(distances, [1.0, 2.5f64.sqrt(), 2.0]);
+        assert_eq!(forces, [6.0, 2.0, 0.5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "No table MISSING found in test.table")]
+    fn missing_section_is_reported() {
+        read_section(TABLE, "MISSING", "test.table");
+    }
+}
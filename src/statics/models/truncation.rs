@@ -0,0 +1,192 @@
+use std::f64::consts::PI;
+
+use nalgebra::Matrix3;
+use rayon::prelude::*;
+use yaml_rust::Yaml;
+
+use crate::io::input::to_f64;
+use crate::statics::models::CalculatePotential;
+use crate::system::atom::Atom;
+
+// How a pair potential is brought to zero at its cutoff
+pub enum Truncation {
+    Plain,       // V(r) cut at rc, both energy and force jump there
+    Shift,       // V(r) - V(rc), continuous energy
+    ForceShift,  // V(r) - V(rc) + (r - rc) F(rc), continuous energy and force
+    Switch(f64), // V(r) S(r), smoothly switched off between the inner radius and rc
+}
+
+impl Truncation {
+    pub fn from(definition: &Yaml) -> Truncation {
+        match definition["truncation"].as_str() {
+            Some("plain") | None => Truncation::Plain,
+            Some("shift") => Truncation::Shift,
+            Some("force_shift") => Truncation::ForceShift,
+            Some("switch") => match &definition["inner_radius"] {
+                Yaml::BadValue => panic!("Switch truncation requires an inner_radius"),
+                inner_radius => Truncation::Switch(to_f64(inner_radius)),
+            },
+            _ => panic!("Unknown truncation"),
+        }
+    }
+    pub fn check_cutoff(&self, cutoff: f64) {
+        if let Truncation::Switch(inner_radius) = self {
+            if *inner_radius <= 0.0 || *inner_radius >= cutoff {
+                panic!(
+                    "Switching inner radius ({}) must lie between 0 and the cutoff ({})",
+                    inner_radius, cutoff
+                );
+            }
+        }
+    }
+    // Truncated pair energy and force magnitude, the force being positive when repulsive
+    pub fn evaluate(&self, pair: &impl CalculatePotential, distance: f64) -> (f64, f64) {
+        let cutoff = pair.cutoff();
+        if distance >= cutoff {
+            return (0.0, 0.0);
+        }
+        let potential = pair.calculate_potential(distance);
+        let force = pair.calculate_force(distance);
+        match self {
+            Truncation::Plain => (potential, force),
+            Truncation::Shift => (potential - pair.calculate_potential(cutoff), force),
+            Truncation::ForceShift => {
+                let cutoff_force = pair.calculate_force(cutoff);
+                (
+                    potential - pair.calculate_potential(cutoff)
+                        + (distance - cutoff) * cutoff_force,
+                    force - cutoff_force,
+                )
+            }
+            Truncation::Switch(inner_radius) => match distance <= *inner_radius {
+                true => (potential, force),
+                false => {
+                    // S(r) = (rc^2 - r^2)^2 (rc^2 + 2 r^2 - 3 ri^2) / (rc^2 - ri^2)^3
+                    let r2 = distance.powi(2);
+                    let rc2 = cutoff.powi(2);
+                    let ri2 = inner_radius.powi(2);
+                    let denominator = (rc2 - ri2).powi(3);
+                    let switch = (rc2 - r2).powi(2) * (rc2 + 2.0 * r2 - 3.0 * ri2) / denominator;
+                    let switch_derivative = 12.0 * distance * (rc2 - r2) * (ri2 - r2) / denominator;
+                    (
+                        potential * switch,
+                        force * switch - potential * switch_derivative,
+                    )
+                }
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Truncation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Truncation::Plain => write!(f, "plain truncation"),
+            Truncation::Shift => write!(f, "energy-shifted truncation"),
+            Truncation::ForceShift => write!(f, "force-shifted truncation"),
+            Truncation::Switch(x) => write!(f, "switched truncation from {}", x),
+        }
+    }
+}
+
+// Tail corrections assume the potential is left untouched up to the cutoff
+pub fn parse_tail_correction(definition: &Yaml) -> bool {
+    let tail_correction = match &definition["tail_correction"] {
+        Yaml::Boolean(x) => *x,
+        Yaml::BadValue => false,
+        _ => panic!("tail_correction must be a boolean"),
+    };
+    if tail_correction && !matches!(Truncation::from(definition), Truncation::Plain) {
+        panic!("Tail corrections require plain truncation");
+    }
+    tail_correction
+}
+
+// Adds the energy and virial of pairs beyond the cutoff, assuming a uniform density there:
+//   E_tail = 2 pi / V sum_ab N_a N_b int_rc^inf r^2 V_ab(r) dr
+//   W_tail = -2 pi / (3 V) sum_ab N_a N_b int_rc^inf r^3 V'_ab(r) dr
+// Each atom of species a takes the terms involving a, so that the totals add up. Species are
+// given by index for every atom.
+pub fn apply_tail_corrections(
+    atoms: &mut [Atom],
+    species: &[usize],
+    species_count: usize,
+    volume: f64,
+    integrals: &(dyn Fn(usize, usize) -> (f64, f64) + Sync),
+) {
+    let mut counts = vec![0; species_count];
+    species.iter().for_each(|x| counts[*x] += 1);
+    let corrections = (0..species_count)
+        .map(|first| {
+            counts
+                .iter()
+                .enumerate()
+                .fold((0.0, 0.0), |(energy, virial), (second, count)| {
+                    let (energy_integral, virial_integral) = integrals(first, second);
+                    (
+                        energy + 2.0 * PI / volume * *count as f64 * energy_integral,
+                        virial - 2.0 * PI / (3.0 * volume) * *count as f64 * virial_integral,
+                    )
+                })
+        })
+        .collect::<Vec<(f64, f64)>>();
+    atoms
+        .par_iter_mut()
+        .zip(species.par_iter())
+        .for_each(|(atom, species)| {
+            let (energy, virial) = corrections[*species];
+            atom.current.potential_energy += energy;
+            atom.current.virial += Matrix3::identity() * virial;
+        });
+}
+
+// Integral of r^n exp(-k (r - rc)) from rc to infinity, which is
+//   sum_m n! / (n - m)! rc^(n - m) / k^(m + 1)
+pub fn exponential_tail(power: i32, decay: f64, cutoff: f64) -> f64 {
+    (0..=power)
+        .fold((0.0, 1.0), |(total, factor), m| {
+            (
+                total + factor * cutoff.powi(power - m) / decay.powi(m + 1),
+                factor * (power - m) as f64,
+            )
+        })
+        .0
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    // Settings of every truncation scheme, to be inserted in a potential definition
+    pub fn truncation_settings(inner_radius: f64) -> [String; 4] {
+        [
+            "truncation: plain".to_string(),
+            "truncation: shift".to_string(),
+            "truncation: force_shift".to_string(),
+            format!("truncation: switch, inner_radius: {:.3}", inner_radius),
+        ]
+    }
+
+    // Compares the truncated force with -dE/dr from central differences of the truncated energy
+    pub fn check_forces(
+        truncation: &Truncation,
+        pair: &impl CalculatePotential,
+        distances: &[f64],
+    ) {
+        let step = 1e-5;
+        for distance in distances {
+            let (_, force) = truncation.evaluate(pair, *distance);
+            let numerical_force = -(truncation.evaluate(pair, distance + step).0
+                - truncation.evaluate(pair, distance - step).0)
+                / (2.0 * step);
+            assert!(
+                (force - numerical_force).abs() < 1e-6 * force.abs().max(1.0),
+                "{} at r = {}: force {} instead of {}",
+                truncation,
+                distance,
+                force,
+                numerical_force
+            );
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use periodic_table_on_an_enum::Element;
-use nalgebra::Vector3;
+use nalgebra::{Matrix3, Vector3};
 
 use crate::io::input::to_vec3;
 
@@ -8,6 +8,7 @@ pub struct CurrentState {
     pub position: Vector3<f64>,
     pub velocity: Vector3<f64>,
     pub force: Vector3<f64>,
+    pub virial: Matrix3<f64>,
     pub potential_energy: f64,
     pub kinetic_energy: f64,
     pub total_energy: f64,
@@ -18,6 +19,7 @@ pub struct PreviousState {
     pub position: Vector3<f64>,
     pub velocity: Vector3<f64>,
     pub force: Vector3<f64>,
+    pub virial: Matrix3<f64>,
     pub potential_energy: f64,
     pub kinetic_energy: f64,
     pub total_energy: f64,
@@ -29,6 +31,7 @@ impl CurrentState {
             position: self.position,
             velocity: self.velocity,
             force: self.force,
+            virial: self.virial,
             potential_energy: self.potential_energy,
             kinetic_energy: self.kinetic_energy,
             total_energy: self.total_energy,
@@ -44,6 +47,7 @@ pub struct Atom {
     pub current: CurrentState,
     pub mass: f64,
     pub charge: f64,
+    pub image: [i64; 3], // Periodic images crossed along each box vector since the start
 }
 
 impl Atom {
@@ -54,6 +58,7 @@ impl Atom {
                 position: Vector3::zeros(),
                 velocity: Vector3::zeros(),
                 force: Vector3::zeros(),
+                virial: Matrix3::zeros(),
                 potential_energy: 0.0,
                 kinetic_energy: 0.0,
                 total_energy: 0.0,
@@ -62,12 +67,14 @@ impl Atom {
                 position: Vector3::zeros(),
                 velocity: Vector3::zeros(),
                 force: Vector3::zeros(),
+                virial: Matrix3::zeros(),
                 potential_energy: 0.0,
                 kinetic_energy: 0.0,
                 total_energy: 0.0,
             },
             mass: 1.0,
             charge: 0.0,
+            image: [0, 0, 0],
             name: String::from("NaN"),
         }
     }
@@ -112,6 +119,7 @@ impl Atom {
                 position: self.current.position,
                 velocity: self.current.velocity,
                 force: self.current.force,
+                virial: self.current.virial,
                 potential_energy: self.current.potential_energy,
                 kinetic_energy: self.current.kinetic_energy,
                 total_energy: self.current.total_energy,
@@ -120,12 +128,14 @@ impl Atom {
                 position: self.previous.position,
                 velocity: self.previous.velocity,
                 force: self.previous.force,
+                virial: self.previous.virial,
                 potential_energy: self.previous.potential_energy,
                 kinetic_energy: self.previous.kinetic_energy,
                 total_energy: self.previous.total_energy,
             },
             mass: self.mass,
             charge: self.charge,
+            image: self.image,
         }
     }
 }
@@ -9,25 +9,27 @@ pub struct SimulationBox {
     pub vectors: Matrix3<f64>,                // Simulation box vectors
     pub versors: Matrix3<f64>,                // Simulation box versors
     pub dimensions: Vector3<f64>,             // Dimensions of the simulation box
-    pub replicas: [usize; 3],                 // Number of replicas in each direction
+    pub supercell: Matrix3<i64>,              // Box vectors in terms of the unit cell vectors
     pub periodicity: [bool; 3],               // Periodicity of the simulation box
     pub change_of_basis_matrix: Matrix3<f64>, // Matrix mapping between global coordinates and simulation box coordinates
+    pub fractional_matrix: Matrix3<f64>,      // Maps cartesian to fractional coordinates
 }
 
 impl SimulationBox {
     pub fn new(
         vectors: Matrix3<f64>,
         periodicity: [bool; 3],
-        replicas: [usize; 3],
+        supercell: Matrix3<i64>,
     ) -> SimulationBox {
         let mut new_box = SimulationBox {
             cell: UnitCell::new(vectors),
             vectors: Matrix3::zeros(),
             versors: Matrix3::zeros(),
             dimensions: Vector3::zeros(),
-            replicas,
+            supercell,
             periodicity,
             change_of_basis_matrix: Matrix3::zeros(),
+            fractional_matrix: Matrix3::zeros(),
         };
         new_box.calculate_box_vectors();
         new_box.calculate_mapping_matrix();
@@ -35,37 +37,109 @@ impl SimulationBox {
     }
 
     fn calculate_box_vectors(&mut self) -> () {
-        let mut new_vectors = self.cell.vectors.clone();
-        for i in 0..3 {
-            new_vectors[(i, i)] *= self.replicas[i] as f64;
-        }
+        // Rows of the supercell matrix pick the unit cell vectors spanning each box vector
+        self.vectors = self.supercell.map(|x| x as f64) * self.cell.vectors;
+        self.calculate_dimensions();
+    }
+
+    fn calculate_dimensions(&mut self) {
         let new_dimensions: Vector3<f64> = Vector3::new(
-            new_vectors.row(0).norm(),
-            new_vectors.row(1).norm(),
-            new_vectors.row(2).norm(),
+            self.vectors.row(0).norm(),
+            self.vectors.row(1).norm(),
+            self.vectors.row(2).norm(),
         );
         let mut new_versors = Matrix3::<f64>::zeros();
         for i in 0..3 {
             for j in 0..3 {
-                new_versors[(i, j)] = new_vectors[(i, j)] * (1.0 / new_dimensions[i]);
+                new_versors[(i, j)] = self.vectors[(i, j)] * (1.0 / new_dimensions[i]);
             }
         }
-        self.vectors = new_vectors;
         self.dimensions = new_dimensions;
         self.versors = new_versors;
     }
 
+    // Applies the linear map r -> deformation * r to the box and its unit cell
+    pub fn deform(&mut self, deformation: &Matrix3<f64>) {
+        self.cell = UnitCell::new(self.cell.vectors * deformation.transpose());
+        self.vectors *= deformation.transpose();
+        self.calculate_dimensions();
+        self.calculate_mapping_matrix();
+    }
+
     fn calculate_mapping_matrix(&mut self) -> () {
         let change_of_basis_matrix = self.vectors.normalize();
         match change_of_basis_matrix.try_inverse() {
             Some(_) => self.change_of_basis_matrix = change_of_basis_matrix,
             None => panic!("Could not invert change of basis matrix"),
         }
+        // Box vectors are stored as rows, so r = vectors^T * s for fractional coordinates s
+        match self.vectors.transpose().try_inverse() {
+            Some(inverse) => self.fractional_matrix = inverse,
+            None => panic!("Could not invert simulation box vectors"),
+        }
+    }
+
+    pub fn volume(&self) -> f64 {
+        self.vectors.determinant().abs()
+    }
+
+    // Distances between opposite faces of the box, i.e. V / |a_j x a_k| for each vector a_i
+    pub fn perpendicular_widths(&self) -> Vector3<f64> {
+        let volume = self.volume();
+        Vector3::from_fn(|i, _| {
+            let a: Vector3<f64> = self.vectors.row((i + 1) % 3).transpose();
+            let b: Vector3<f64> = self.vectors.row((i + 2) % 3).transpose();
+            volume / a.cross(&b).norm()
+        })
+    }
+
+    pub fn to_fractional(&self, position: &Vector3<f64>) -> Vector3<f64> {
+        self.fractional_matrix * position
+    }
+
+    pub fn to_cartesian(&self, fractional_position: &Vector3<f64>) -> Vector3<f64> {
+        self.vectors.transpose() * fractional_position
+    }
+
+    // Brings the position back into the box along periodic directions, returning the number
+    // of box vectors it was shifted by in each of them
+    pub fn wrap_position(&self, position: Vector3<f64>) -> (Vector3<f64>, [i64; 3]) {
+        let mut fractional_position = self.to_fractional(&position);
+        let mut image = [0i64; 3];
+        for i in 0..3 {
+            if !self.periodicity[i] {
+                continue;
+            }
+            let shift = fractional_position[i].floor();
+            fractional_position[i] -= shift;
+            image[i] = shift as i64;
+            // Tiny negative coordinates round up to exactly one after the shift
+            if fractional_position[i] >= 1.0 {
+                fractional_position[i] -= 1.0;
+                image[i] += 1;
+            }
+        }
+        match image == [0, 0, 0] {
+            true => (position, image),
+            false => (self.to_cartesian(&fractional_position), image),
+        }
+    }
+
+    // Shortest periodic image of a separation vector. Rounding fractional coordinates gives the
+    // true minimum image for any separation shorter than half of the perpendicular widths.
+    pub fn minimum_image(&self, distance_vector: Vector3<f64>) -> Vector3<f64> {
+        let mut fractional_vector = self.to_fractional(&distance_vector);
+        for i in 0..3 {
+            if self.periodicity[i] {
+                fractional_vector[i] -= fractional_vector[i].round();
+            }
+        }
+        self.to_cartesian(&fractional_vector)
     }
 
-    pub fn wrap_position(&self, position: Vector3<f64>) -> Vector3<f64> {
-        let wrapped_position = position.clone();
-        wrapped_position
+    // Position the atom would have if it had never been wrapped back into the box
+    pub fn unwrap_position(&self, position: &Vector3<f64>, image: &[i64; 3]) -> Vector3<f64> {
+        position + self.to_cartesian(&Vector3::from_fn(|i, _| image[i] as f64))
     }
 
     pub fn map_vector_to_box_basis(&self, vector: &Vector3<f64>) -> Vector3<f64> {
@@ -80,9 +154,92 @@ impl SimulationBox {
 impl std::fmt::Display for SimulationBox {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let definition = format!(
-            "Vectors: {:?}\n  Replicas: {:?}\n  Periodicity: {:?}\n",
-            self.vectors, self.replicas, self.periodicity
+            "Vectors: {:?}\n  Supercell: {:?}\n  Periodicity: {:?}\n",
+            self.vectors, self.supercell, self.periodicity
         );
         write!(f, "{}", definition)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    fn skewed_box(periodicity: [bool; 3]) -> SimulationBox {
+        SimulationBox::new(
+            Matrix3::new(10.0, 0.0, 0.0, 8.5, 3.0, 0.0, -7.0, 2.5, 4.0),
+            periodicity,
+            Matrix3::identity(),
+        )
+    }
+
+    // Shortest of the 27 images of the separation once brought into the box, searching only
+    // along periodic directions
+    fn brute_force_image(
+        simulation_box: &SimulationBox,
+        distance_vector: Vector3<f64>,
+    ) -> Vector3<f64> {
+        let fractional_vector = simulation_box.to_fractional(&distance_vector);
+        let wrapped = Vector3::from_fn(|i, _| match simulation_box.periodicity[i] {
+            true => fractional_vector[i] - fractional_vector[i].floor(),
+            false => fractional_vector[i],
+        });
+        (0..27i64)
+            .map(|k| Vector3::new(k / 9 - 1, k / 3 % 3 - 1, k % 3 - 1))
+            .filter(|shift| (0..3).all(|i| simulation_box.periodicity[i] || shift[i] == 0))
+            .map(|shift| simulation_box.to_cartesian(&(wrapped + shift.map(|x| x as f64))))
+            .min_by(|a, b| a.norm().partial_cmp(&b.norm()).unwrap())
+            .unwrap()
+    }
+
+    // Separations shorter than half of the smallest perpendicular width, displaced by a few
+    // box vectors along periodic directions
+    fn check_minimum_images(simulation_box: &SimulationBox) {
+        let widths = simulation_box.perpendicular_widths();
+        let radius = 0.5 * widths.min();
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..2000 {
+            let separation = loop {
+                let candidate = Vector3::from_fn(|_, _| rng.gen_range(-radius..radius));
+                if candidate.norm() < radius {
+                    break candidate;
+                }
+            };
+            let shift = Vector3::from_fn(|i, _| match simulation_box.periodicity[i] {
+                true => rng.gen_range(-3..=3) as f64,
+                false => 0.0,
+            });
+            let distance_vector = separation + simulation_box.to_cartesian(&shift);
+            let image = simulation_box.minimum_image(distance_vector);
+            let expected = brute_force_image(simulation_box, distance_vector);
+            assert!(
+                (image - expected).norm() < 1e-9,
+                "{} instead of {}",
+                image,
+                expected
+            );
+            assert!((image - separation).norm() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn minimum_image_in_skewed_box() {
+        let simulation_box = skewed_box([true, true, true]);
+        let widths = simulation_box.perpendicular_widths();
+        // The cell is skewed enough for the widths to be well below the vector lengths
+        assert!(widths.min() < 0.5 * simulation_box.dimensions.min());
+        check_minimum_images(&simulation_box);
+    }
+
+    #[test]
+    fn minimum_image_leaves_non_periodic_direction() {
+        let simulation_box = skewed_box([true, true, false]);
+        check_minimum_images(&simulation_box);
+        let distance_vector = simulation_box.to_cartesian(&Vector3::new(0.0, 0.0, 2.7));
+        let image = simulation_box.minimum_image(distance_vector);
+        assert!((image - distance_vector).norm() < 1e-9);
+    }
+}
@@ -1,69 +1,94 @@
 use rayon::prelude::*;
 
-use nalgebra::Vector3;
+use nalgebra::{Matrix3, Vector3};
 
 use crate::system::atom::Atom;
 use crate::system::r#box::SimulationBox;
 
+// Converts fractional coordinates of the unit cell into cartesian ones
 pub fn scale_cell_basis(atoms: &mut Vec<Atom>, simulation_box: &SimulationBox) -> () {
     println!("Scaling cell basis");
+    let cell_vectors = simulation_box.cell.vectors.transpose();
     atoms.par_iter_mut().for_each(|atom| {
-        let mut scaled_position = Vector3::<f64>::zeros();
-        for i in 0..3 {
-            scaled_position[i] = atom.current.position[i] * simulation_box.cell.vectors[(i, i)];
-        }
-        atom.current.position = scaled_position;
+        atom.current.position = cell_vectors * atom.current.position;
+    });
+}
+
+// Lattice points of the unit cell (integer combinations of its vectors) lying inside the box,
+// i.e. whose fractional coordinates with respect to the supercell vectors fall within [0, 1)
+fn supercell_lattice_points(supercell: &Matrix3<i64>) -> Vec<Vector3<i64>> {
+    let supercell = supercell.map(|x| x as f64);
+    let fractional_mapping = match supercell.transpose().try_inverse() {
+        Some(inverse) => inverse,
+        None => panic!("Supercell matrix must not be singular"),
+    };
+    // Bounding range taken from the corners of the box, in unit cell coordinates
+    let corners = (0..8)
+        .map(|k| {
+            (0..3)
+                .filter(|i| k & (1 << i) != 0)
+                .map(|i| supercell.row(i).transpose())
+                .sum::<Vector3<f64>>()
+        })
+        .collect::<Vec<Vector3<f64>>>();
+    let lower = Vector3::from_fn(|i, _| {
+        corners
+            .iter()
+            .map(|x| x[i])
+            .fold(f64::MAX, f64::min)
+            .floor() as i64
     });
+    let upper = Vector3::from_fn(|i, _| {
+        corners.iter().map(|x| x[i]).fold(f64::MIN, f64::max).ceil() as i64
+    });
+    let tolerance = 1e-9;
+    let mut lattice_points = Vec::new();
+    // Bounds are inclusive, as negative supercell vectors reach the origin from below
+    for x in lower[0]..=upper[0] {
+        for y in lower[1]..=upper[1] {
+            for z in lower[2]..=upper[2] {
+                let lattice_point = Vector3::new(x, y, z);
+                let fractional_position = fractional_mapping * lattice_point.map(|x| x as f64);
+                if fractional_position
+                    .iter()
+                    .all(|s| *s > -tolerance && *s < 1.0 - tolerance)
+                {
+                    lattice_points.push(lattice_point);
+                }
+            }
+        }
+    }
+    let expected_count = supercell.determinant().abs().round() as usize;
+    if lattice_points.len() != expected_count {
+        panic!(
+            "Found {} lattice points in the supercell, expected {}",
+            lattice_points.len(),
+            expected_count
+        );
+    }
+    lattice_points
 }
 
 pub fn generate_lattice(atoms: &mut Vec<Atom>, simulation_box: &SimulationBox) -> () {
     println!("Generating lattice");
     let original_atoms_length = atoms.len();
-    let x_replicas = simulation_box.replicas[0];
-    let y_replicas = simulation_box.replicas[1];
-    let z_replicas = simulation_box.replicas[2];
-
-    let x_vector: Vector3<f64> = Vector3::new(
-        simulation_box.cell.vectors[(0, 0)],
-        simulation_box.cell.vectors[(0, 1)],
-        simulation_box.cell.vectors[(0, 2)],
-    );
-
-    let y_vector: Vector3<f64> = Vector3::new(
-        simulation_box.cell.vectors[(1, 0)],
-        simulation_box.cell.vectors[(1, 1)],
-        simulation_box.cell.vectors[(1, 2)],
-    );
-
-    let z_vector: Vector3<f64> = Vector3::new(
-        simulation_box.cell.vectors[(2, 0)],
-        simulation_box.cell.vectors[(2, 1)],
-        simulation_box.cell.vectors[(2, 2)],
-    );
+    let cell_vectors = simulation_box.cell.vectors.transpose();
 
     let mut generated_atoms = Vec::new();
-    for x in 0..x_replicas {
-        for y in 0..y_replicas {
-            for z in 0..z_replicas {
-                if x == 0 && y == 0 && z == 0 {
-                    continue;
-                }
-                let mut replica_atoms: Vec<Atom> = atoms
-                    .par_iter()
-                    .map(|atom| {
-                        let mut new_atom = atom.clone();
-                        new_atom.current.position +=
-                            x as f64 * x_vector;
-                        new_atom.current.position +=
-                            y as f64 * y_vector;
-                        new_atom.current.position +=
-                            z as f64 * z_vector;
-                        new_atom
-                    })
-                    .collect::<Vec<Atom>>();
-                generated_atoms.append(&mut replica_atoms);
-            }
+    for lattice_point in supercell_lattice_points(&simulation_box.supercell) {
+        if lattice_point == Vector3::zeros() {
+            continue;
         }
+        let translation = cell_vectors * lattice_point.map(|x| x as f64);
+        let mut replica_atoms: Vec<Atom> = atoms
+            .par_iter()
+            .map(|atom| {
+                let mut new_atom = atom.clone();
+                new_atom.current.position += translation;
+                new_atom
+            })
+            .collect::<Vec<Atom>>();
+        generated_atoms.append(&mut replica_atoms);
     }
     atoms.append(&mut generated_atoms);
     let new_atoms_length = atoms.len();
@@ -79,3 +104,35 @@ pub fn generate_lattice(atoms: &mut Vec<Atom>, simulation_box: &SimulationBox) -
         .enumerate()
         .for_each(|(index, atom)| atom.id = index as u64);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_lattice_points(supercell: Matrix3<i64>) {
+        let lattice_points = supercell_lattice_points(&supercell);
+        let supercell = supercell.map(|x| x as f64);
+        let fractional_mapping = supercell.transpose().try_inverse().unwrap();
+        let expected_count = supercell.determinant().abs().round() as usize;
+        assert_eq!(lattice_points.len(), expected_count);
+        assert!(lattice_points.contains(&Vector3::zeros()));
+        for (index, lattice_point) in lattice_points.iter().enumerate() {
+            let fractional_position = fractional_mapping * lattice_point.map(|x| x as f64);
+            assert!(fractional_position
+                .iter()
+                .all(|s| *s > -1e-9 && *s < 1.0 - 1e-9));
+            assert!(!lattice_points[..index].contains(lattice_point));
+        }
+    }
+
+    #[test]
+    fn lattice_points_of_diagonal_supercell() {
+        check_lattice_points(Matrix3::new(2, 0, 0, 0, 3, 0, 0, 0, 1));
+    }
+
+    #[test]
+    fn lattice_points_of_negative_determinant_supercell() {
+        check_lattice_points(Matrix3::new(-2, 0, 0, 0, 1, 0, 0, 0, 1));
+        check_lattice_points(Matrix3::new(1, 1, 0, -1, 1, 0, 0, 0, -2));
+    }
+}
@@ -2,8 +2,9 @@ pub mod r#box;
 pub mod atom;
 pub mod lattice;
 pub mod cell;
+pub mod topology;
 
-use nalgebra::Matrix3;
+use nalgebra::{Matrix3, Vector3};
 
 use rayon::prelude::*;
 use yaml_rust::Yaml;
@@ -15,6 +16,7 @@ use crate::system::r#box::SimulationBox;
 use crate::system::atom::Atom;
 use crate::system::lattice::generate_lattice;
 use crate::system::lattice::scale_cell_basis;
+use crate::system::topology::Topology;
 
 use crate::utils::metrics::UnitSystem;
 
@@ -22,13 +24,18 @@ pub struct SystemDefinition {
     pub simulation_box: SimulationBox, // Box origin and vectors
     pub atoms: Vec<Atom>,              // Atom type, position, velocity, etc.
     pub units: UnitSystem,             // Unit systems i.e. conversion factors
+    pub topology: Topology,            // Bonds, angles, dihedrals and impropers
 }
 
 impl SystemDefinition {
     pub fn from(system_definition: &Yaml) -> SystemDefinition {
         let mut new_system = SystemDefinition::initialize_system(system_definition);
         scale_cell_basis(&mut new_system.atoms, &new_system.simulation_box);
+        let unit_cell_atoms = new_system.atoms.len();
         generate_lattice(&mut new_system.atoms, &new_system.simulation_box);
+        new_system
+            .topology
+            .replicate(new_system.atoms.len() / unit_cell_atoms, unit_cell_atoms);
         new_system.atoms.par_iter_mut().for_each(|atom| {
             atom.previous = atom.current.cache();
         });
@@ -68,29 +75,58 @@ impl SystemDefinition {
             Yaml::BadValue => [1, 1, 1],
             Yaml::Array(x) => match x.len() {
                 3 => [
-                    x[0].as_i64().unwrap() as usize,
-                    x[1].as_i64().unwrap() as usize,
-                    x[2].as_i64().unwrap() as usize,
+                    x[0].as_i64().unwrap(),
+                    x[1].as_i64().unwrap(),
+                    x[2].as_i64().unwrap(),
                 ],
                 _ => panic!("Unknown replicas"),
             },
             _ => panic!("Unknown replicas"),
         };
+        // A general supercell matrix takes the place of the replicas along each vector
+        let supercell = match &config["supercell"] {
+            Yaml::BadValue => Matrix3::from_diagonal(&Vector3::from(unit_cell_replications)),
+            Yaml::Array(rows) => match (rows.len(), &config["replicas"]) {
+                (3, Yaml::BadValue) => Matrix3::from_row_iterator(rows.iter().flat_map(|row| {
+                    match row.as_vec() {
+                        Some(row) if row.len() == 3 => row
+                            .iter()
+                            .map(|x| x.as_i64().expect("Supercell entries must be integers"))
+                            .collect::<Vec<i64>>(),
+                        _ => panic!("Supercell rows must have three entries"),
+                    }
+                })),
+                (3, _) => panic!("Replicas and supercell cannot be used together"),
+                _ => panic!("Supercell matrix must have three rows"),
+            },
+            _ => panic!("Unknown supercell"),
+        };
+        let atoms = load_atoms(&config["atoms"]);
+        let topology = Topology::from(&config["topology"], atoms.len());
         SystemDefinition {
             simulation_box: SimulationBox::new(
-                Matrix3::from_vec(box_vectors),
+                Matrix3::from_row_slice(&box_vectors),
                 box_periodicity,
-                unit_cell_replications,
+                supercell,
             ),
-            atoms: load_atoms(&config["atoms"]),
+            atoms,
             units: UnitSystem::new(&config["units"]),
+            topology,
         }
     }
     pub fn wrap_atom_positions(&mut self) -> () {
         self.atoms.par_iter_mut().for_each(|atom| {
-            atom.current.position = self.simulation_box.wrap_position(atom.current.position)
+            let (position, image) = self.simulation_box.wrap_position(atom.current.position);
+            atom.current.position = position;
+            for (total, shift) in atom.image.iter_mut().zip(image) {
+                *total += shift;
+            }
         });
     }
+    pub fn unwrapped_position(&self, atom: &Atom) -> Vector3<f64> {
+        self.simulation_box
+            .unwrap_position(&atom.current.position, &atom.image)
+    }
 }
 
 impl std::fmt::Display for SystemDefinition {
@@ -109,3 +145,46 @@ impl std::fmt::Display for SystemDefinition {
         )
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use periodic_table_on_an_enum::Element;
+
+    use super::*;
+
+    // Named atoms at cartesian positions in a cube of the given side, in atomic units,
+    // bonded by the topology
+    pub fn molecules(
+        side: f64,
+        periodicity: [bool; 3],
+        atoms: &[(&str, Vector3<f64>)],
+        mut topology: Topology,
+    ) -> SystemDefinition {
+        let atoms = atoms
+            .iter()
+            .enumerate()
+            .map(|(id, (name, position))| {
+                let mut atom = Atom::new();
+                atom.id = id as u64;
+                atom.name = name.to_string();
+                atom.mass = Element::from_symbol(name).unwrap().get_atomic_mass().into();
+                atom.current.position = *position;
+                atom.previous = atom.current.cache();
+                atom
+            })
+            .collect::<Vec<Atom>>();
+        topology.replicate(1, atoms.len());
+        let mut system = SystemDefinition {
+            simulation_box: SimulationBox::new(
+                Matrix3::from_diagonal_element(side),
+                periodicity,
+                Matrix3::identity(),
+            ),
+            atoms,
+            units: UnitSystem::new(&Yaml::String("atomic".to_string())),
+            topology,
+        };
+        system.wrap_atom_positions();
+        system
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::VecDeque;
+use std::fs::read_to_string;
+
+use yaml_rust::Yaml;
+
+// Group of bonded atoms with the type naming its coefficients in the potential section
+#[derive(Clone)]
+pub struct BondedGroup<const N: usize> {
+    pub kind: String,
+    pub atoms: [usize; N], // Indices of the atoms, in the order defining the interaction
+}
+
+// Molecular connectivity, given in the system section as lists of entries
+//   bonds: [{type: oh, atoms: [1, 2]}, ...]
+// with atom ids starting at 1, and/or read from the Bonds, Angles, Dihedrals and Impropers
+// sections of a LAMMPS data file (`file`). The groups of the unit cell are repeated in
+// every replica of the lattice.
+pub struct Topology {
+    pub bonds: Vec<BondedGroup<2>>,
+    pub angles: Vec<BondedGroup<3>>,
+    pub dihedrals: Vec<BondedGroup<4>>,
+    pub impropers: Vec<BondedGroup<4>>,
+    // Atoms one, two and three bonds away from each atom, with their separation,
+    // sorted by index. Empty without bonds.
+    special: Vec<Vec<(usize, u8)>>,
+}
+
+impl Topology {
+    pub fn new() -> Topology {
+        Topology {
+            bonds: Vec::new(),
+            angles: Vec::new(),
+            dihedrals: Vec::new(),
+            impropers: Vec::new(),
+            special: Vec::new(),
+        }
+    }
+    pub fn from(definition: &Yaml, atoms_count: usize) -> Topology {
+        let mut topology = Topology::new();
+        if let Yaml::BadValue = definition {
+            return topology;
+        }
+        if let Some(filename) = definition["file"].as_str() {
+            let content = match read_to_string(filename) {
+                Ok(x) => x,
+                Err(_) => panic!("Failed to read topology file {}", filename),
+            };
+            topology.bonds = read_data_section(&content, "Bonds", filename);
+            topology.angles = read_data_section(&content, "Angles", filename);
+            topology.dihedrals = read_data_section(&content, "Dihedrals", filename);
+            topology.impropers = read_data_section(&content, "Impropers", filename);
+        }
+        topology.bonds.extend(read_groups(&definition["bonds"]));
+        topology.angles.extend(read_groups(&definition["angles"]));
+        topology
+            .dihedrals
+            .extend(read_groups(&definition["dihedrals"]));
+        topology
+            .impropers
+            .extend(read_groups(&definition["impropers"]));
+        topology.check_atoms(atoms_count);
+        topology
+    }
+    fn check_atoms(&self, atoms_count: usize) {
+        let indices = self
+            .bonds
+            .iter()
+            .flat_map(|x| x.atoms.iter())
+            .chain(self.angles.iter().flat_map(|x| x.atoms.iter()))
+            .chain(self.dihedrals.iter().flat_map(|x| x.atoms.iter()))
+            .chain(self.impropers.iter().flat_map(|x| x.atoms.iter()));
+        for index in indices {
+            if *index >= atoms_count {
+                panic!(
+                    "Topology refers to atom {} but only {} atoms are defined",
+                    index + 1,
+                    atoms_count
+                );
+            }
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.bonds.is_empty()
+            && self.angles.is_empty()
+            && self.dihedrals.is_empty()
+            && self.impropers.is_empty()
+    }
+    // Copies the groups into every replica, whose atoms follow the unit cell ones in blocks
+    pub fn replicate(&mut self, replicas_count: usize, atoms_per_replica: usize) {
+        fn repeat<const N: usize>(
+            groups: &mut Vec<BondedGroup<N>>,
+            replicas_count: usize,
+            atoms_per_replica: usize,
+        ) {
+            let unit_groups = groups.clone();
+            for replica in 1..replicas_count {
+                groups.extend(unit_groups.iter().map(|group| BondedGroup {
+                    kind: group.kind.clone(),
+                    atoms: group.atoms.map(|x| x + replica * atoms_per_replica),
+                }));
+            }
+        }
+        repeat(&mut self.bonds, replicas_count, atoms_per_replica);
+        repeat(&mut self.angles, replicas_count, atoms_per_replica);
+        repeat(&mut self.dihedrals, replicas_count, atoms_per_replica);
+        repeat(&mut self.impropers, replicas_count, atoms_per_replica);
+        self.find_special_pairs(replicas_count * atoms_per_replica);
+    }
+    // Breadth-first search along the bonds, keeping the shortest separation of each pair
+    fn find_special_pairs(&mut self, atoms_count: usize) {
+        if self.bonds.is_empty() {
+            self.special = Vec::new();
+            return;
+        }
+        let mut bonded = vec![Vec::new(); atoms_count];
+        for bond in &self.bonds {
+            let [a, b] = bond.atoms;
+            bonded[a].push(b);
+            bonded[b].push(a);
+        }
+        self.special = (0..atoms_count)
+            .map(|start| {
+                let mut separations = vec![(start, 0u8)];
+                let mut queue = VecDeque::from([(start, 0u8)]);
+                while let Some((atom, separation)) = queue.pop_front() {
+                    if separation == 3 {
+                        continue;
+                    }
+                    for next in &bonded[atom] {
+                        if separations.iter().all(|(x, _)| x != next) {
+                            separations.push((*next, separation + 1));
+                            queue.push_back((*next, separation + 1));
+                        }
+                    }
+                }
+                separations.remove(0);
+                separations.sort_unstable();
+                separations
+            })
+            .collect();
+    }
+    // Number of bonds between two atoms: 1, 2 or 3 for 1-2, 1-3 and 1-4 pairs, 0 otherwise
+    pub fn separation(&self, first: usize, second: usize) -> u8 {
+        if self.special.is_empty() {
+            return 0;
+        }
+        match self.special[first].binary_search_by_key(&second, |(x, _)| *x) {
+            Ok(position) => self.special[first][position].1,
+            Err(_) => 0,
+        }
+    }
+}
+
+pub fn read_kind(yaml: &Yaml) -> String {
+    match yaml {
+        Yaml::String(x) => x.clone(),
+        Yaml::Integer(x) => x.to_string(),
+        _ => panic!("Bonded interaction types must be names or integers"),
+    }
+}
+
+fn read_groups<const N: usize>(yaml: &Yaml) -> Vec<BondedGroup<N>> {
+    let entries = match yaml {
+        Yaml::BadValue => return Vec::new(),
+        Yaml::Array(x) => x,
+        _ => panic!("Topology entries must be lists"),
+    };
+    entries
+        .iter()
+        .map(|entry| {
+            let ids = match entry["atoms"].as_vec() {
+                Some(ids) if ids.len() == N => ids,
+                _ => panic!("Topology entries must list {} atom ids", N),
+            };
+            BondedGroup {
+                kind: read_kind(&entry["type"]),
+                atoms: std::array::from_fn(|i| match ids[i].as_i64() {
+                    Some(id) if id >= 1 => id as usize - 1,
+                    _ => panic!("Atom ids of the topology start at 1"),
+                }),
+            }
+        })
+        .collect()
+}
+
+// Lines `id type atom1 ... atomN` following a section header of a LAMMPS data file,
+// up to the next blank line
+fn read_data_section<const N: usize>(
+    content: &str,
+    section: &str,
+    filename: &str,
+) -> Vec<BondedGroup<N>> {
+    let mut lines = content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .skip_while(|line| *line != section)
+        .skip(1)
+        .skip_while(|line| line.is_empty());
+    let mut groups = Vec::new();
+    while let Some(line) = lines.next().filter(|line| !line.is_empty()) {
+        let tokens = line.split_whitespace().collect::<Vec<&str>>();
+        if tokens.len() < N + 2 {
+            panic!("Incomplete {} line in {}: {}", section, filename, line);
+        }
+        groups.push(BondedGroup {
+            kind: tokens[1].to_string(),
+            atoms: std::array::from_fn(|i| match tokens[i + 2].parse::<usize>() {
+                Ok(id) if id >= 1 => id - 1,
+                _ => panic!("Invalid atom id {} in {}", tokens[i + 2], filename),
+            }),
+        });
+    }
+    groups
+}
+
+#[cfg(test)]
+pub mod tests {
+    use yaml_rust::YamlLoader;
+
+    use super::*;
+
+    // Groups of one type over atom indices starting at 0
+    pub fn groups<const N: usize>(kind: &str, atoms: &[[usize; N]]) -> Vec<BondedGroup<N>> {
+        atoms
+            .iter()
+            .map(|atoms| BondedGroup {
+                kind: kind.to_string(),
+                atoms: *atoms,
+            })
+            .collect()
+    }
+
+    // Carbon chain 0-1-2-3 with a hydrogen 4 on the first carbon
+    pub fn butane() -> Topology {
+        let mut topology = Topology::new();
+        topology.bonds = [
+            groups("cc", &[[0, 1], [1, 2], [2, 3]]),
+            groups("ch", &[[0, 4]]),
+        ]
+        .concat();
+        topology.angles = [
+            groups("ccc", &[[0, 1, 2], [1, 2, 3]]),
+            groups("hcc", &[[4, 0, 1]]),
+        ]
+        .concat();
+        topology.dihedrals = [
+            groups("cccc", &[[0, 1, 2, 3]]),
+            groups("hccc", &[[4, 0, 1, 2]]),
+        ]
+        .concat();
+        topology.impropers = groups("x", &[[1, 0, 2, 4]]);
+        topology
+    }
+
+    #[test]
+    fn special_pairs_follow_bonds() {
+        let mut topology = butane();
+        topology.replicate(1, 5);
+        let expected = [
+            (0, 1, 1),
+            (0, 2, 2),
+            (0, 3, 3),
+            (0, 4, 1),
+            (1, 2, 1),
+            (1, 3, 2),
+            (1, 4, 2),
+            (2, 3, 1),
+            (2, 4, 3),
+            (3, 4, 0),
+        ];
+        for (first, second, separation) in expected {
+            assert_eq!(topology.separation(first, second), separation);
+            assert_eq!(topology.separation(second, first), separation);
+        }
+    }
+
+    #[test]
+    fn replicas_repeat_groups_and_special_pairs() {
+        let definition = YamlLoader::load_from_str(
+            "
+            bonds: [{type: oh, atoms: [1, 2]}, {type: oh, atoms: [1, 3]}]
+            angles: [{type: hoh, atoms: [2, 1, 3]}]
+            ",
+        )
+        .unwrap();
+        let mut topology = Topology::from(&definition[0], 3);
+        topology.replicate(3, 3);
+        assert_eq!(
+            topology
+                .bonds
+                .iter()
+                .map(|bond| bond.atoms)
+                .collect::<Vec<[usize; 2]>>(),
+            vec![[0, 1], [0, 2], [3, 4], [3, 5], [6, 7], [6, 8]]
+        );
+        assert_eq!(
+            topology
+                .angles
+                .iter()
+                .map(|angle| angle.atoms)
+                .collect::<Vec<[usize; 3]>>(),
+            vec![[1, 0, 2], [4, 3, 5], [7, 6, 8]]
+        );
+        assert!(topology.angles.iter().all(|angle| angle.kind == "hoh"));
+        assert_eq!(topology.separation(7, 8), 2);
+        assert_eq!(topology.separation(6, 7), 1);
+        // Replicas are not bonded to each other
+        assert_eq!(topology.separation(2, 3), 0);
+    }
+
+    #[test]
+    fn data_sections_are_read_until_blank_line() {
+        let content = "
+LAMMPS data file
+
+5 atoms
+4 bonds
+1 angles
+
+Bonds # connectivity
+
+1 1 1 2
+2 1 2 3 # backbone
+3 1 3 4
+4 2 1 5
+
+Angles
+
+1 3 1 2 3
+";
+        let bonds: Vec<BondedGroup<2>> = read_data_section(content, "Bonds", "test.data");
+        assert_eq!(bonds.len(), 4);
+        assert_eq!(bonds[1].kind, "1");
+        assert_eq!(bonds[1].atoms, [1, 2]);
+        assert_eq!(bonds[3].kind, "2");
+        assert_eq!(bonds[3].atoms, [0, 4]);
+        let angles: Vec<BondedGroup<3>> = read_data_section(content, "Angles", "test.data");
+        assert_eq!(angles.len(), 1);
+        assert_eq!(angles[0].atoms, [0, 1, 2]);
+        let dihedrals: Vec<BondedGroup<4>> = read_data_section(content, "Dihedrals", "test.data");
+        assert!(dihedrals.is_empty());
+    }
+}
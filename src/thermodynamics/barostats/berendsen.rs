@@ -0,0 +1,50 @@
+use nalgebra::Matrix3;
+
+use crate::io::input::to_f64;
+use crate::statics::energetics::calculate_pressure_tensor;
+use crate::system::SystemDefinition;
+use crate::thermodynamics::barostats::{
+    deform_system, parse_coupling_time, parse_target_pressure, PressureCoupling,
+};
+
+pub struct BerendsenBarostat {
+    pub pressure: f64,
+    pub coupling_time: f64,
+    pub compressibility: f64, // In inverse pressure units
+    pub coupling: PressureCoupling,
+}
+
+impl BerendsenBarostat {
+    pub fn from(yaml: &yaml_rust::Yaml) -> BerendsenBarostat {
+        let compressibility = to_f64(&yaml["compressibility"]);
+        if compressibility <= 0.0 {
+            panic!("Compressibility must be positive");
+        }
+        BerendsenBarostat {
+            pressure: parse_target_pressure(yaml),
+            coupling_time: parse_coupling_time(yaml),
+            compressibility,
+            coupling: PressureCoupling::from(&yaml["coupling"]),
+        }
+    }
+    pub fn apply(&self, system: &mut SystemDefinition, timestep: f64) {
+        let pressure_difference =
+            Matrix3::identity() * self.pressure - calculate_pressure_tensor(system);
+        let deformation = Matrix3::identity()
+            - self
+                .coupling
+                .project(&pressure_difference, &system.simulation_box.periodicity)
+                * (self.compressibility * timestep / (3.0 * self.coupling_time));
+        deform_system(system, &deformation);
+    }
+}
+
+impl std::fmt::Display for BerendsenBarostat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Berendsen barostat at {} ({} coupling, coupling time: {})",
+            self.pressure, self.coupling, self.coupling_time
+        )
+    }
+}
@@ -0,0 +1,152 @@
+pub mod berendsen;
+pub mod parrinello_rahman;
+
+use nalgebra::Matrix3;
+use rayon::prelude::*;
+
+use crate::io::input::to_f64;
+use crate::system::SystemDefinition;
+
+pub enum PressureCoupling {
+    Isotropic,
+    Anisotropic,
+    Triclinic,
+}
+
+impl PressureCoupling {
+    pub fn from(yaml: &yaml_rust::Yaml) -> PressureCoupling {
+        match yaml.as_str() {
+            Some("isotropic") | None => PressureCoupling::Isotropic,
+            Some("anisotropic") => PressureCoupling::Anisotropic,
+            Some("triclinic") => PressureCoupling::Triclinic,
+            _ => panic!("Unknown pressure coupling"),
+        }
+    }
+
+    // Keeps only the components of a tensor that the coupling is allowed to act on,
+    // leaving the non-periodic directions of the box untouched
+    pub fn project(&self, tensor: &Matrix3<f64>, periodicity: &[bool; 3]) -> Matrix3<f64> {
+        let mut projected = Matrix3::<f64>::zeros();
+        match self {
+            PressureCoupling::Isotropic => {
+                let periodic_directions = periodicity.iter().filter(|x| **x).count();
+                if periodic_directions == 0 {
+                    return projected;
+                }
+                let average = (0..3)
+                    .filter(|i| periodicity[*i])
+                    .map(|i| tensor[(i, i)])
+                    .sum::<f64>()
+                    / periodic_directions as f64;
+                (0..3)
+                    .filter(|i| periodicity[*i])
+                    .for_each(|i| projected[(i, i)] = average);
+            }
+            PressureCoupling::Anisotropic => (0..3)
+                .filter(|i| periodicity[*i])
+                .for_each(|i| projected[(i, i)] = tensor[(i, i)]),
+            PressureCoupling::Triclinic => {
+                for i in 0..3 {
+                    for j in 0..3 {
+                        if periodicity[i] && periodicity[j] {
+                            projected[(i, j)] = 0.5 * (tensor[(i, j)] + tensor[(j, i)]);
+                        }
+                    }
+                }
+            }
+        }
+        projected
+    }
+}
+
+impl std::fmt::Display for PressureCoupling {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PressureCoupling::Isotropic => write!(f, "isotropic"),
+            PressureCoupling::Anisotropic => write!(f, "anisotropic"),
+            PressureCoupling::Triclinic => write!(f, "triclinic"),
+        }
+    }
+}
+
+pub enum Barostat {
+    Berendsen(berendsen::BerendsenBarostat),
+    ParrinelloRahman(parrinello_rahman::ParrinelloRahmanBarostat),
+}
+
+impl Barostat {
+    pub fn from(yaml: &yaml_rust::Yaml, temperature: f64) -> Barostat {
+        match yaml["type"].as_str() {
+            Some("berendsen") => Barostat::Berendsen(berendsen::BerendsenBarostat::from(yaml)),
+            Some("parrinello_rahman") | Some("mtk") => Barostat::ParrinelloRahman(
+                parrinello_rahman::ParrinelloRahmanBarostat::from(yaml, temperature),
+            ),
+            _ => panic!("Unknown barostat"),
+        }
+    }
+    // Called before the integrator step, only barostats split around it act here
+    pub fn prepare(
+        &mut self,
+        system: &mut SystemDefinition,
+        degrees_of_freedom: usize,
+        timestep: f64,
+    ) {
+        if let Barostat::ParrinelloRahman(x) = self {
+            x.prepare(system, degrees_of_freedom, timestep)
+        }
+    }
+    pub fn apply(
+        &mut self,
+        system: &mut SystemDefinition,
+        degrees_of_freedom: usize,
+        timestep: f64,
+    ) {
+        match self {
+            Barostat::Berendsen(x) => x.apply(system, timestep),
+            Barostat::ParrinelloRahman(x) => x.apply(system, degrees_of_freedom, timestep),
+        }
+    }
+    pub fn reservoir_energy(&self, system: &SystemDefinition) -> f64 {
+        match self {
+            Barostat::Berendsen(_) => 0.0,
+            Barostat::ParrinelloRahman(x) => x.reservoir_energy(system),
+        }
+    }
+}
+
+impl std::fmt::Display for Barostat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Barostat::Berendsen(x) => write!(f, "{}", x),
+            Barostat::ParrinelloRahman(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+pub fn parse_target_pressure(yaml: &yaml_rust::Yaml) -> f64 {
+    to_f64(&yaml["pressure"])
+}
+
+pub fn parse_coupling_time(yaml: &yaml_rust::Yaml) -> f64 {
+    let coupling_time = to_f64(&yaml["coupling_time"]);
+    if coupling_time <= 0.0 {
+        panic!("Barostat coupling time must be positive");
+    }
+    coupling_time
+}
+
+// Maps the box and every atom position through r -> deformation * r
+pub fn deform_system(system: &mut SystemDefinition, deformation: &Matrix3<f64>) {
+    system.simulation_box.deform(deformation);
+    system
+        .atoms
+        .par_iter_mut()
+        .for_each(|atom| atom.current.position = deformation * atom.current.position);
+}
+
+pub fn symmetric_matrix_exponential(matrix: &Matrix3<f64>) -> Matrix3<f64> {
+    let decomposition = matrix.symmetric_eigen();
+    decomposition.eigenvectors
+        * Matrix3::from_diagonal(&decomposition.eigenvalues.map(|x| x.exp()))
+        * decomposition.eigenvectors.transpose()
+}
@@ -0,0 +1,118 @@
+use nalgebra::Matrix3;
+use rayon::prelude::*;
+
+use crate::statics::energetics::{calculate_kinetic_energy, calculate_pressure_tensor};
+use crate::system::SystemDefinition;
+use crate::thermodynamics::barostats::{
+    deform_system, parse_coupling_time, parse_target_pressure, symmetric_matrix_exponential,
+    PressureCoupling,
+};
+
+// Parrinello-Rahman cell dynamics in the Martyna-Tobias-Klein form, split in two half steps
+// around the velocity Verlet step with the box propagated before the new forces are evaluated
+pub struct ParrinelloRahmanBarostat {
+    pub pressure: f64,
+    pub coupling_time: f64,
+    pub temperature: f64,
+    pub coupling: PressureCoupling,
+    cell_velocity: Matrix3<f64>,
+    mass: f64,
+}
+
+impl ParrinelloRahmanBarostat {
+    pub fn from(yaml: &yaml_rust::Yaml, temperature: f64) -> ParrinelloRahmanBarostat {
+        ParrinelloRahmanBarostat {
+            pressure: parse_target_pressure(yaml),
+            coupling_time: parse_coupling_time(yaml),
+            temperature,
+            coupling: PressureCoupling::from(&yaml["coupling"]),
+            cell_velocity: Matrix3::zeros(),
+            mass: 0.0,
+        }
+    }
+
+    // W dv_g/dt = V (P - P_0) + 2 K / N_f, with pressures taken in energy per volume
+    fn update_cell_velocity(
+        &mut self,
+        system: &SystemDefinition,
+        degrees_of_freedom: usize,
+        half_timestep: f64,
+    ) {
+        let thermal_energy = system.units.boltzmann_constant() * self.temperature;
+        if self.mass == 0.0 {
+            self.mass =
+                (degrees_of_freedom + 3) as f64 * thermal_energy * self.coupling_time.powi(2);
+        }
+        let pressure_factor = system.units.pressure_factor();
+        let kinetic_energy = calculate_kinetic_energy(&system.atoms, &system.units);
+        let driving_force = (calculate_pressure_tensor(system)
+            - Matrix3::identity() * self.pressure)
+            * (system.simulation_box.volume() / pressure_factor)
+            + Matrix3::identity() * (2.0 * kinetic_energy / degrees_of_freedom as f64);
+        self.cell_velocity += self
+            .coupling
+            .project(&driving_force, &system.simulation_box.periodicity)
+            * (half_timestep / self.mass);
+    }
+
+    fn scale_velocities(
+        &self,
+        system: &mut SystemDefinition,
+        degrees_of_freedom: usize,
+        half_timestep: f64,
+    ) {
+        let friction = self.cell_velocity
+            + Matrix3::identity() * (self.cell_velocity.trace() / degrees_of_freedom as f64);
+        let scaling = symmetric_matrix_exponential(&(-friction * half_timestep));
+        system
+            .atoms
+            .par_iter_mut()
+            .for_each(|atom| atom.current.velocity = scaling * atom.current.velocity);
+    }
+
+    pub fn prepare(
+        &mut self,
+        system: &mut SystemDefinition,
+        degrees_of_freedom: usize,
+        timestep: f64,
+    ) {
+        if degrees_of_freedom == 0 {
+            return;
+        }
+        self.update_cell_velocity(system, degrees_of_freedom, 0.5 * timestep);
+        self.scale_velocities(system, degrees_of_freedom, 0.5 * timestep);
+        deform_system(
+            system,
+            &symmetric_matrix_exponential(&(self.cell_velocity * timestep)),
+        );
+    }
+
+    pub fn apply(
+        &mut self,
+        system: &mut SystemDefinition,
+        degrees_of_freedom: usize,
+        timestep: f64,
+    ) {
+        if degrees_of_freedom == 0 {
+            return;
+        }
+        self.scale_velocities(system, degrees_of_freedom, 0.5 * timestep);
+        self.update_cell_velocity(system, degrees_of_freedom, 0.5 * timestep);
+    }
+
+    // Kinetic energy of the cell and the P_0 V work term of the extended Hamiltonian
+    pub fn reservoir_energy(&self, system: &SystemDefinition) -> f64 {
+        0.5 * self.mass * (self.cell_velocity.transpose() * self.cell_velocity).trace()
+            + self.pressure * system.simulation_box.volume() / system.units.pressure_factor()
+    }
+}
+
+impl std::fmt::Display for ParrinelloRahmanBarostat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Parrinello-Rahman barostat at {} ({} coupling, coupling time: {})",
+            self.pressure, self.coupling, self.coupling_time
+        )
+    }
+}
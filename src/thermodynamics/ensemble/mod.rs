@@ -1,14 +1,65 @@
+pub mod npt;
 pub mod nve;
+pub mod nvt;
+
+use crate::system::SystemDefinition;
 
 pub enum Ensemble {
     NVE(nve::NVE),
+    NVT(nvt::NVT),
+    NPT(npt::NPT),
 }
 
 impl Ensemble {
     pub fn from(yaml: &yaml_rust::Yaml) -> Ensemble {
         match yaml["type"].as_str().unwrap() {
             "nve" => Ensemble::NVE(nve::NVE::from(&yaml)),
+            "nvt" => Ensemble::NVT(nvt::NVT::from(yaml)),
+            "npt" => Ensemble::NPT(npt::NPT::from(yaml)),
             _ => panic!("Unknown ensemble type"),
         }
     }
+    pub fn prepare(
+        &mut self,
+        system: &mut SystemDefinition,
+        degrees_of_freedom: usize,
+        timestep: f64,
+    ) {
+        match self {
+            Ensemble::NVE(_) => {}
+            Ensemble::NVT(x) => x.thermostat.prepare(system, degrees_of_freedom, timestep),
+            Ensemble::NPT(x) => {
+                x.thermostat.prepare(system, degrees_of_freedom, timestep);
+                x.barostat.prepare(system, degrees_of_freedom, timestep);
+            }
+        }
+    }
+    pub fn update(
+        &mut self,
+        system: &mut SystemDefinition,
+        degrees_of_freedom: usize,
+        timestep: f64,
+    ) {
+        match self {
+            Ensemble::NVE(_) => {}
+            Ensemble::NVT(x) => x.thermostat.apply(system, degrees_of_freedom, timestep),
+            Ensemble::NPT(x) => {
+                x.barostat.apply(system, degrees_of_freedom, timestep);
+                x.thermostat.apply(system, degrees_of_freedom, timestep);
+            }
+        }
+    }
+    pub fn reservoir_energy(&self, system: &SystemDefinition, degrees_of_freedom: usize) -> f64 {
+        match self {
+            Ensemble::NVE(_) => 0.0,
+            Ensemble::NVT(x) => x
+                .thermostat
+                .reservoir_energy(&system.units, degrees_of_freedom),
+            Ensemble::NPT(x) => {
+                x.thermostat
+                    .reservoir_energy(&system.units, degrees_of_freedom)
+                    + x.barostat.reservoir_energy(system)
+            }
+        }
+    }
 }
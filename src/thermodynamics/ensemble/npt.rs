@@ -0,0 +1,19 @@
+use crate::thermodynamics::barostats::Barostat;
+use crate::thermodynamics::thermostats::{parse_target_temperature, Thermostat};
+
+pub struct NPT {
+    pub thermostat: Thermostat,
+    pub barostat: Barostat,
+}
+
+impl NPT {
+    pub fn from(yaml: &yaml_rust::Yaml) -> NPT {
+        let thermostat = Thermostat::from(yaml);
+        let barostat = Barostat::from(&yaml["barostat"], parse_target_temperature(yaml));
+        println!("NPT ensemble created with {} and {}", thermostat, barostat);
+        NPT {
+            thermostat,
+            barostat,
+        }
+    }
+}
@@ -0,0 +1,13 @@
+use crate::thermodynamics::thermostats::Thermostat;
+
+pub struct NVT {
+    pub thermostat: Thermostat,
+}
+
+impl NVT {
+    pub fn from(yaml: &yaml_rust::Yaml) -> NVT {
+        let thermostat = Thermostat::from(yaml);
+        println!("NVT ensemble created with {}", thermostat);
+        NVT { thermostat }
+    }
+}
@@ -1,14 +1,60 @@
+pub mod barostats;
 pub mod ensemble;
+pub mod thermostats;
+pub mod velocity;
+
+use crate::dynamics::constraints::Constraints;
+use crate::system::SystemDefinition;
 
 pub struct Thermodynamics {
     pub ensemble: ensemble::Ensemble,
+    pub velocity: Option<velocity::VelocityInitializer>,
+    pub constrained_degrees_of_freedom: usize, // Removed by the integrator constraints
+    pub conserves_momentum: bool, // Whether the integrator keeps the removed momentum at zero
 }
 
 impl Thermodynamics {
     pub fn from(yaml: &yaml_rust::Yaml) -> Thermodynamics {
         Thermodynamics {
             ensemble: ensemble::Ensemble::from(&yaml["ensemble"]),
+            velocity: match &yaml["velocity"] {
+                yaml_rust::Yaml::BadValue => None,
+                velocity_definition => {
+                    Some(velocity::VelocityInitializer::from(velocity_definition))
+                }
+            },
+            constrained_degrees_of_freedom: 0,
+            conserves_momentum: true,
+        }
+    }
+    pub fn initialize(&self, system: &mut SystemDefinition, constraints: Option<&Constraints>) {
+        if let Some(velocity_initializer) = &self.velocity {
+            println!("Initializing velocities: {}", velocity_initializer);
+            velocity_initializer.apply(system, constraints);
         }
     }
-    pub fn update(&mut self) -> () {}
+    pub fn degrees_of_freedom(&self, atoms_count: usize) -> usize {
+        // Momentum removed at the start only stays zero under momentum-conserving integrators
+        let removed_degrees_of_freedom = match &self.velocity {
+            Some(velocity::VelocityInitializer::Boltzmann(x)) if self.conserves_momentum => {
+                x.removed_degrees_of_freedom()
+            }
+            _ => 0,
+        };
+        (3 * atoms_count)
+            .saturating_sub(removed_degrees_of_freedom + self.constrained_degrees_of_freedom)
+    }
+    pub fn prepare(&mut self, system: &mut SystemDefinition, timestep: f64) {
+        let degrees_of_freedom = self.degrees_of_freedom(system.atoms.len());
+        self.ensemble.prepare(system, degrees_of_freedom, timestep);
+    }
+    pub fn update(&mut self, system: &mut SystemDefinition, timestep: f64) {
+        let degrees_of_freedom = self.degrees_of_freedom(system.atoms.len());
+        self.ensemble.update(system, degrees_of_freedom, timestep);
+    }
+    // Energy held by extended-system degrees of freedom, e.g. thermostat chains
+    pub fn reservoir_energy(&self, system: &SystemDefinition) -> f64 {
+        let degrees_of_freedom = self.degrees_of_freedom(system.atoms.len());
+        self.ensemble.reservoir_energy(system, degrees_of_freedom)
+    }
 }
@@ -0,0 +1,42 @@
+use crate::statics::energetics::{calculate_kinetic_energy, calculate_temperature};
+use crate::system::SystemDefinition;
+use crate::thermodynamics::thermostats::{
+    parse_coupling_time, parse_target_temperature, rescale_velocities,
+};
+
+pub struct BerendsenThermostat {
+    pub temperature: f64,
+    pub coupling_time: f64,
+}
+
+impl BerendsenThermostat {
+    pub fn from(yaml: &yaml_rust::Yaml) -> BerendsenThermostat {
+        BerendsenThermostat {
+            temperature: parse_target_temperature(yaml),
+            coupling_time: parse_coupling_time(yaml),
+        }
+    }
+    pub fn apply(&self, system: &mut SystemDefinition, degrees_of_freedom: usize, timestep: f64) {
+        let kinetic_energy = calculate_kinetic_energy(&system.atoms, &system.units);
+        let current_temperature =
+            calculate_temperature(kinetic_energy, degrees_of_freedom, &system.units);
+        if current_temperature <= 0.0 {
+            return;
+        }
+        let scaling_factor = (1.0
+            + timestep / self.coupling_time * (self.temperature / current_temperature - 1.0))
+            .max(0.0)
+            .sqrt();
+        rescale_velocities(&mut system.atoms, scaling_factor);
+    }
+}
+
+impl std::fmt::Display for BerendsenThermostat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Berendsen thermostat at {} (coupling time: {})",
+            self.temperature, self.coupling_time
+        )
+    }
+}
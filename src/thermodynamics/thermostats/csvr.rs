@@ -0,0 +1,84 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rand_distr::{ChiSquared, Distribution, StandardNormal};
+use yaml_rust::Yaml;
+
+use crate::statics::energetics::calculate_kinetic_energy;
+use crate::system::SystemDefinition;
+use crate::thermodynamics::thermostats::{
+    parse_coupling_time, parse_target_temperature, rescale_velocities,
+};
+
+// Canonical sampling through velocity rescaling (Bussi, Donadio and Parrinello, 2007)
+pub struct StochasticVelocityRescalingThermostat {
+    pub temperature: f64,
+    pub coupling_time: f64,
+    pub seed: u64,
+    generator: StdRng,
+}
+
+impl StochasticVelocityRescalingThermostat {
+    pub fn from(yaml: &Yaml) -> StochasticVelocityRescalingThermostat {
+        let seed = match &yaml["seed"] {
+            Yaml::Integer(x) => *x as u64,
+            Yaml::BadValue => rand::random::<u64>(),
+            _ => panic!("Seed must be an integer"),
+        };
+        StochasticVelocityRescalingThermostat {
+            temperature: parse_target_temperature(yaml),
+            coupling_time: parse_coupling_time(yaml),
+            seed,
+            generator: StdRng::seed_from_u64(seed),
+        }
+    }
+    fn sum_of_squared_gaussians(&mut self, count: usize) -> f64 {
+        match count {
+            0 => 0.0,
+            _ => ChiSquared::new(count as f64)
+                .unwrap()
+                .sample(&mut self.generator),
+        }
+    }
+    pub fn apply(
+        &mut self,
+        system: &mut SystemDefinition,
+        degrees_of_freedom: usize,
+        timestep: f64,
+    ) {
+        let kinetic_energy = calculate_kinetic_energy(&system.atoms, &system.units);
+        if kinetic_energy <= 0.0 || degrees_of_freedom == 0 {
+            return;
+        }
+        let target_kinetic_energy =
+            0.5 * degrees_of_freedom as f64 * system.units.boltzmann_constant() * self.temperature;
+        let decay = (-timestep / self.coupling_time).exp();
+        let first_gaussian: f64 = StandardNormal.sample(&mut self.generator);
+        let remaining_gaussians = self.sum_of_squared_gaussians(degrees_of_freedom - 1);
+        let new_kinetic_energy = kinetic_energy
+            + (1.0 - decay)
+                * (target_kinetic_energy * (first_gaussian.powi(2) + remaining_gaussians)
+                    / degrees_of_freedom as f64
+                    - kinetic_energy)
+            + 2.0
+                * first_gaussian
+                * (target_kinetic_energy / degrees_of_freedom as f64
+                    * kinetic_energy
+                    * (1.0 - decay)
+                    * decay)
+                    .sqrt();
+        rescale_velocities(
+            &mut system.atoms,
+            (new_kinetic_energy.max(0.0) / kinetic_energy).sqrt(),
+        );
+    }
+}
+
+impl std::fmt::Display for StochasticVelocityRescalingThermostat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Stochastic velocity rescaling thermostat at {} (coupling time: {}, seed: {})",
+            self.temperature, self.coupling_time, self.seed
+        )
+    }
+}
@@ -0,0 +1,94 @@
+pub mod berendsen;
+pub mod csvr;
+pub mod nose_hoover;
+
+use rayon::prelude::*;
+
+use crate::io::input::to_f64;
+use crate::system::atom::Atom;
+use crate::system::SystemDefinition;
+use crate::utils::metrics::UnitSystem;
+
+pub enum Thermostat {
+    Berendsen(berendsen::BerendsenThermostat),
+    StochasticVelocityRescaling(Box<csvr::StochasticVelocityRescalingThermostat>),
+    NoseHooverChain(Box<nose_hoover::NoseHooverChainThermostat>),
+}
+
+impl Thermostat {
+    pub fn from(yaml: &yaml_rust::Yaml) -> Thermostat {
+        match yaml["thermostat"].as_str() {
+            Some("berendsen") => Thermostat::Berendsen(berendsen::BerendsenThermostat::from(yaml)),
+            Some("csvr") => Thermostat::StochasticVelocityRescaling(Box::new(
+                csvr::StochasticVelocityRescalingThermostat::from(yaml),
+            )),
+            Some("nose_hoover") | Some("nhc") => Thermostat::NoseHooverChain(Box::new(
+                nose_hoover::NoseHooverChainThermostat::from(yaml),
+            )),
+            _ => panic!("Unknown thermostat"),
+        }
+    }
+    // Called before the integrator step, only thermostats split around it act here
+    pub fn prepare(
+        &mut self,
+        system: &mut SystemDefinition,
+        degrees_of_freedom: usize,
+        timestep: f64,
+    ) {
+        if let Thermostat::NoseHooverChain(x) = self {
+            x.half_step(system, degrees_of_freedom, timestep)
+        }
+    }
+    pub fn apply(
+        &mut self,
+        system: &mut SystemDefinition,
+        degrees_of_freedom: usize,
+        timestep: f64,
+    ) {
+        match self {
+            Thermostat::Berendsen(x) => x.apply(system, degrees_of_freedom, timestep),
+            Thermostat::StochasticVelocityRescaling(x) => {
+                x.apply(system, degrees_of_freedom, timestep)
+            }
+            Thermostat::NoseHooverChain(x) => x.half_step(system, degrees_of_freedom, timestep),
+        }
+    }
+    pub fn reservoir_energy(&self, unit_system: &UnitSystem, degrees_of_freedom: usize) -> f64 {
+        match self {
+            Thermostat::NoseHooverChain(x) => x.reservoir_energy(unit_system, degrees_of_freedom),
+            _ => 0.0,
+        }
+    }
+}
+
+impl std::fmt::Display for Thermostat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Thermostat::Berendsen(x) => write!(f, "{}", x),
+            Thermostat::StochasticVelocityRescaling(x) => write!(f, "{}", x),
+            Thermostat::NoseHooverChain(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+pub fn parse_target_temperature(yaml: &yaml_rust::Yaml) -> f64 {
+    let temperature = to_f64(&yaml["temperature"]);
+    if temperature < 0.0 {
+        panic!("Thermostat temperature must not be negative");
+    }
+    temperature
+}
+
+pub fn parse_coupling_time(yaml: &yaml_rust::Yaml) -> f64 {
+    let coupling_time = to_f64(&yaml["coupling_time"]);
+    if coupling_time <= 0.0 {
+        panic!("Thermostat coupling time must be positive");
+    }
+    coupling_time
+}
+
+pub fn rescale_velocities(atoms: &mut [Atom], scaling_factor: f64) {
+    atoms
+        .par_iter_mut()
+        .for_each(|atom| atom.current.velocity *= scaling_factor);
+}
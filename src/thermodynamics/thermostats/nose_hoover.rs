@@ -0,0 +1,220 @@
+use yaml_rust::Yaml;
+
+use crate::statics::energetics::calculate_kinetic_energy;
+use crate::system::SystemDefinition;
+use crate::thermodynamics::thermostats::{
+    parse_coupling_time, parse_target_temperature, rescale_velocities,
+};
+use crate::utils::metrics::UnitSystem;
+
+const DEFAULT_CHAIN_LENGTH: usize = 3;
+
+// Third order Suzuki-Yoshida weights used to split every chain propagation
+const SUZUKI_YOSHIDA_WEIGHTS: [f64; 3] = [
+    1.351_207_191_959_657_8,
+    -1.702_414_383_919_315_6,
+    1.351_207_191_959_657_8,
+];
+
+// Nose-Hoover chain propagated in two half steps around the velocity Verlet step
+// (Martyna, Tuckerman, Tobias and Klein, 1996)
+pub struct NoseHooverChainThermostat {
+    pub temperature: f64,
+    pub coupling_time: f64,
+    pub chain_length: usize,
+    positions: Vec<f64>,
+    velocities: Vec<f64>,
+    masses: Vec<f64>,
+}
+
+impl NoseHooverChainThermostat {
+    pub fn from(yaml: &Yaml) -> NoseHooverChainThermostat {
+        let chain_length = match &yaml["chain_length"] {
+            Yaml::Integer(x) if *x > 0 => *x as usize,
+            Yaml::BadValue => DEFAULT_CHAIN_LENGTH,
+            _ => panic!("Chain length must be a positive integer"),
+        };
+        NoseHooverChainThermostat {
+            temperature: parse_target_temperature(yaml),
+            coupling_time: parse_coupling_time(yaml),
+            chain_length,
+            positions: vec![0.0; chain_length],
+            velocities: vec![0.0; chain_length],
+            masses: Vec::new(),
+        }
+    }
+
+    // Q_1 = N_f k_B T tau^2 and Q_j = k_B T tau^2 for the rest of the chain
+    fn initialize_masses(&mut self, thermal_energy: f64, degrees_of_freedom: usize) {
+        let base_mass = thermal_energy * self.coupling_time.powi(2);
+        self.masses = (0..self.chain_length)
+            .map(|j| match j {
+                0 => degrees_of_freedom as f64 * base_mass,
+                _ => base_mass,
+            })
+            .collect();
+    }
+
+    pub fn half_step(
+        &mut self,
+        system: &mut SystemDefinition,
+        degrees_of_freedom: usize,
+        timestep: f64,
+    ) {
+        if degrees_of_freedom == 0 {
+            return;
+        }
+        let thermal_energy = system.units.boltzmann_constant() * self.temperature;
+        if self.masses.is_empty() {
+            self.initialize_masses(thermal_energy, degrees_of_freedom);
+        }
+        let target = degrees_of_freedom as f64 * thermal_energy;
+        let last = self.chain_length - 1;
+        let mut twice_kinetic_energy = 2.0 * calculate_kinetic_energy(&system.atoms, &system.units);
+        let mut forces = vec![(twice_kinetic_energy - target) / self.masses[0]];
+        forces.extend((1..self.chain_length).map(|j| {
+            (self.masses[j - 1] * self.velocities[j - 1].powi(2) - thermal_energy) / self.masses[j]
+        }));
+
+        let mut scaling_factor = 1.0;
+        for weight in SUZUKI_YOSHIDA_WEIGHTS {
+            // Each half step covers a full timestep with the factors of Martyna et al.
+            let delta = weight * timestep;
+            let (delta_2, delta_4, delta_8) = (delta / 2.0, delta / 4.0, delta / 8.0);
+
+            self.velocities[last] += forces[last] * delta_4;
+            for j in (0..last).rev() {
+                let damping = (-delta_8 * self.velocities[j + 1]).exp();
+                self.velocities[j] =
+                    self.velocities[j] * damping.powi(2) + delta_4 * forces[j] * damping;
+            }
+
+            let damping = (-delta_2 * self.velocities[0]).exp();
+            scaling_factor *= damping;
+            twice_kinetic_energy *= damping.powi(2);
+            forces[0] = (twice_kinetic_energy - target) / self.masses[0];
+            for j in 0..self.chain_length {
+                self.positions[j] += self.velocities[j] * delta_2;
+            }
+
+            for j in 0..last {
+                let damping = (-delta_8 * self.velocities[j + 1]).exp();
+                self.velocities[j] =
+                    self.velocities[j] * damping.powi(2) + delta_4 * forces[j] * damping;
+                forces[j + 1] = (self.masses[j] * self.velocities[j].powi(2) - thermal_energy)
+                    / self.masses[j + 1];
+            }
+            self.velocities[last] += forces[last] * delta_4;
+        }
+        rescale_velocities(&mut system.atoms, scaling_factor);
+    }
+
+    // Energy stored in the chain, which together with the system energy is conserved
+    pub fn reservoir_energy(&self, unit_system: &UnitSystem, degrees_of_freedom: usize) -> f64 {
+        if self.masses.is_empty() {
+            return 0.0;
+        }
+        let thermal_energy = unit_system.boltzmann_constant() * self.temperature;
+        let kinetic_part: f64 = self
+            .masses
+            .iter()
+            .zip(self.velocities.iter())
+            .map(|(mass, velocity)| 0.5 * mass * velocity.powi(2))
+            .sum();
+        let potential_part = degrees_of_freedom as f64 * thermal_energy * self.positions[0]
+            + thermal_energy * self.positions[1..].iter().sum::<f64>();
+        kinetic_part + potential_part
+    }
+}
+
+impl std::fmt::Display for NoseHooverChainThermostat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Nose-Hoover chain thermostat at {} (coupling time: {}, chain length: {})",
+            self.temperature, self.coupling_time, self.chain_length
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+    use yaml_rust::YamlLoader;
+
+    use super::*;
+    use crate::statics::energetics::calculate_temperature;
+
+    fn free_atoms(temperature: f64) -> SystemDefinition {
+        let definition = YamlLoader::load_from_str(
+            "
+            cell: [[10.0, 0, 0], [0, 10.0, 0], [0, 0, 10.0]]
+            atoms:
+              - {name: Ar, position: [0.1, 0.1, 0.1]}
+              - {name: Ar, position: [0.6, 0.1, 0.1]}
+              - {name: Ar, position: [0.1, 0.6, 0.1]}
+              - {name: Ar, position: [0.1, 0.1, 0.6]}
+            periodicity: xyz
+            units: atomic
+            ",
+        )
+        .unwrap();
+        let mut system = SystemDefinition::from(&definition[0]);
+        for (index, atom) in system.atoms.iter_mut().enumerate() {
+            let sign = [1.0, -1.0][index % 2];
+            atom.current.velocity = Vector3::new(sign * 300.0, 150.0 - 100.0 * index as f64, 50.0);
+        }
+        let current = calculate_temperature(
+            calculate_kinetic_energy(&system.atoms, &system.units),
+            3 * system.atoms.len(),
+            &system.units,
+        );
+        rescale_velocities(&mut system.atoms, (temperature / current).sqrt());
+        system
+    }
+
+    // Free particles around the target temperature with a single thermostat oscillate with
+    // the period sqrt(2) pi tau of the linearized equations of motion
+    #[test]
+    fn oscillation_period_follows_coupling_time() {
+        let coupling_time = 1e-4;
+        let definition = YamlLoader::load_from_str(&format!(
+            "{{temperature: 300.0, coupling_time: {}, chain_length: 1}}",
+            coupling_time
+        ))
+        .unwrap();
+        let mut thermostat = NoseHooverChainThermostat::from(&definition[0]);
+        let mut system = free_atoms(303.0);
+        let degrees_of_freedom = 3 * system.atoms.len();
+        let timestep = coupling_time / 200.0;
+
+        let mut crossings = Vec::new();
+        let mut previous_deviation = 3.0;
+        for step in 1..4000 {
+            // Free particles are left unchanged by the integrator between the two half steps
+            thermostat.half_step(&mut system, degrees_of_freedom, timestep);
+            thermostat.half_step(&mut system, degrees_of_freedom, timestep);
+            let temperature = calculate_temperature(
+                calculate_kinetic_energy(&system.atoms, &system.units),
+                degrees_of_freedom,
+                &system.units,
+            );
+            let deviation = temperature - 300.0;
+            if previous_deviation <= 0.0 && deviation > 0.0 {
+                // Linear interpolation of the upward crossing
+                let fraction = previous_deviation / (previous_deviation - deviation);
+                crossings.push((step as f64 - 1.0 + fraction) * timestep);
+            }
+            previous_deviation = deviation;
+        }
+        assert!(crossings.len() >= 3);
+        let period = (crossings[crossings.len() - 1] - crossings[0]) / (crossings.len() - 1) as f64;
+        let expected = 2.0_f64.sqrt() * std::f64::consts::PI * coupling_time;
+        assert!(
+            (period / expected - 1.0).abs() < 0.01,
+            "period {} instead of {}",
+            period,
+            expected
+        );
+    }
+}
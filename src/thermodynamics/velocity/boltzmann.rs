@@ -0,0 +1,110 @@
+use nalgebra::Vector3;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rand_distr::{Distribution, Normal};
+use yaml_rust::Yaml;
+
+use crate::dynamics::constraints::Constraints;
+use crate::io::input::to_f64;
+use crate::statics::energetics::{calculate_kinetic_energy, calculate_temperature};
+use crate::system::SystemDefinition;
+use crate::thermodynamics::velocity::{remove_angular_momentum, remove_linear_momentum};
+
+pub struct BoltzmannVelocityDistribution {
+    pub temperature: f64,
+    pub seed: u64,
+    pub remove_linear_momentum: bool,
+    pub remove_angular_momentum: bool,
+}
+
+impl BoltzmannVelocityDistribution {
+    pub fn from(yaml: &Yaml) -> BoltzmannVelocityDistribution {
+        let temperature = to_f64(&yaml["temperature"]);
+        if temperature < 0.0 {
+            panic!("Velocity distribution temperature must not be negative");
+        }
+        BoltzmannVelocityDistribution {
+            temperature,
+            seed: match &yaml["seed"] {
+                Yaml::Integer(x) => *x as u64,
+                Yaml::BadValue => rand::random::<u64>(),
+                _ => panic!("Seed must be an integer"),
+            },
+            remove_linear_momentum: match &yaml["remove_linear_momentum"] {
+                Yaml::Boolean(x) => *x,
+                Yaml::BadValue => true,
+                _ => panic!("remove_linear_momentum must be a boolean"),
+            },
+            remove_angular_momentum: match &yaml["remove_angular_momentum"] {
+                Yaml::Boolean(x) => *x,
+                Yaml::BadValue => false,
+                _ => panic!("remove_angular_momentum must be a boolean"),
+            },
+        }
+    }
+
+    pub fn removed_degrees_of_freedom(&self) -> usize {
+        3 * self.remove_linear_momentum as usize + 3 * self.remove_angular_momentum as usize
+    }
+
+    pub fn apply(&self, system: &mut SystemDefinition, constraints: Option<&Constraints>) {
+        if self.remove_angular_momentum && system.simulation_box.periodicity.iter().any(|x| *x) {
+            panic!("Angular momentum can only be removed for non-periodic systems");
+        }
+        let boltzmann_constant = system.units.boltzmann_constant();
+        let kinetic_energy_factor = system.units.kinetic_energy_factor();
+
+        // Atoms are visited sequentially so that a given seed always yields the same velocities
+        let mut generator = StdRng::seed_from_u64(self.seed);
+        system.atoms.iter_mut().for_each(|atom| {
+            let standard_deviation = (boltzmann_constant * self.temperature
+                / (atom.mass * kinetic_energy_factor))
+                .sqrt();
+            let distribution = Normal::new(0.0, standard_deviation).unwrap();
+            atom.current.velocity = Vector3::new(
+                distribution.sample(&mut generator),
+                distribution.sample(&mut generator),
+                distribution.sample(&mut generator),
+            );
+        });
+        if self.remove_linear_momentum {
+            remove_linear_momentum(&mut system.atoms);
+        }
+        if self.remove_angular_momentum {
+            remove_angular_momentum(&mut system.atoms);
+        }
+        // Velocities along the constraints are removed, which leaves the momentum unchanged
+        let constrained_degrees_of_freedom = match constraints {
+            Some(constraints) => {
+                constraints.constrain_velocities(system, None);
+                constraints.count()
+            }
+            None => 0,
+        };
+
+        // Rescale so that the instantaneous temperature matches the requested one exactly
+        let degrees_of_freedom = (3 * system.atoms.len())
+            .saturating_sub(self.removed_degrees_of_freedom() + constrained_degrees_of_freedom);
+        let kinetic_energy = calculate_kinetic_energy(&system.atoms, &system.units);
+        let current_temperature =
+            calculate_temperature(kinetic_energy, degrees_of_freedom, &system.units);
+        let scaling_factor = match current_temperature > 0.0 {
+            true => (self.temperature / current_temperature).sqrt(),
+            false => 0.0,
+        };
+        system.atoms.iter_mut().for_each(|atom| {
+            atom.current.velocity *= scaling_factor;
+            atom.previous.velocity = atom.current.velocity;
+        });
+    }
+}
+
+impl std::fmt::Display for BoltzmannVelocityDistribution {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Maxwell-Boltzmann velocities at {} (seed: {})",
+            self.temperature, self.seed
+        )
+    }
+}
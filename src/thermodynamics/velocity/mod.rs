@@ -0,0 +1,78 @@
+pub mod boltzmann;
+
+use nalgebra::{Matrix3, Vector3};
+
+use crate::dynamics::constraints::Constraints;
+use crate::system::atom::Atom;
+use crate::system::SystemDefinition;
+
+pub enum VelocityInitializer {
+    Boltzmann(boltzmann::BoltzmannVelocityDistribution),
+}
+
+impl VelocityInitializer {
+    pub fn from(yaml: &yaml_rust::Yaml) -> VelocityInitializer {
+        match yaml["type"].as_str() {
+            Some("boltzmann") | None => {
+                VelocityInitializer::Boltzmann(boltzmann::BoltzmannVelocityDistribution::from(yaml))
+            }
+            _ => panic!("Unknown velocity distribution type"),
+        }
+    }
+    pub fn apply(&self, system: &mut SystemDefinition, constraints: Option<&Constraints>) {
+        match self {
+            VelocityInitializer::Boltzmann(x) => x.apply(system, constraints),
+        }
+    }
+}
+
+impl std::fmt::Display for VelocityInitializer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            VelocityInitializer::Boltzmann(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+fn center_of_mass(atoms: &[Atom]) -> Vector3<f64> {
+    let total_mass: f64 = atoms.iter().map(|atom| atom.mass).sum();
+    atoms
+        .iter()
+        .map(|atom| atom.mass * atom.current.position)
+        .sum::<Vector3<f64>>()
+        / total_mass
+}
+
+pub fn remove_linear_momentum(atoms: &mut [Atom]) {
+    let total_mass: f64 = atoms.iter().map(|atom| atom.mass).sum();
+    let center_of_mass_velocity = atoms
+        .iter()
+        .map(|atom| atom.mass * atom.current.velocity)
+        .sum::<Vector3<f64>>()
+        / total_mass;
+    atoms
+        .iter_mut()
+        .for_each(|atom| atom.current.velocity -= center_of_mass_velocity);
+}
+
+pub fn remove_angular_momentum(atoms: &mut [Atom]) {
+    let center = center_of_mass(atoms);
+    let mut angular_momentum = Vector3::<f64>::zeros();
+    let mut inertia_tensor = Matrix3::<f64>::zeros();
+    for atom in atoms.iter() {
+        let relative_position = atom.current.position - center;
+        angular_momentum += atom.mass * relative_position.cross(&atom.current.velocity);
+        inertia_tensor += atom.mass
+            * (Matrix3::identity() * relative_position.norm_squared()
+                - relative_position * relative_position.transpose());
+    }
+    // Linear or single-atom systems have a singular inertia tensor, nothing to remove then
+    let angular_velocity = match inertia_tensor.try_inverse() {
+        Some(inverse) => inverse * angular_momentum,
+        None => return,
+    };
+    atoms.iter_mut().for_each(|atom| {
+        let relative_position = atom.current.position - center;
+        atom.current.velocity -= angular_velocity.cross(&relative_position);
+    });
+}
@@ -5,6 +5,7 @@ use rayon::prelude::*;
 
 use crate::dynamics::neighbors::NeighborsList;
 use crate::simulation::Simulation;
+use crate::statics::models::PotentialModel;
 
 const DEFAULT_PRECISION: usize = 3;
 
@@ -17,6 +18,12 @@ fn get_header_label(field_name: &str) -> String {
         "x" => "X".to_string(),
         "y" => "Y".to_string(),
         "z" => "Z".to_string(),
+        "xu" => "Xu".to_string(),
+        "yu" => "Yu".to_string(),
+        "zu" => "Zu".to_string(),
+        "ix" => "Ix".to_string(),
+        "iy" => "Iy".to_string(),
+        "iz" => "Iz".to_string(),
         "type" => "Type".to_string(),
         "vx" => "Vx".to_string(),
         "vy" => "Vy".to_string(),
@@ -29,7 +36,19 @@ fn get_header_label(field_name: &str) -> String {
         "potential_energy" => "PotEn".to_string(),
         "kinetic_energy" => "KinEn".to_string(),
         "total_energy" => "TotEn".to_string(),
+        "conserved_energy" => "ConsEn".to_string(),
         "temperature" => "Temp".to_string(),
+        "max_force" => "MaxF".to_string(),
+        "pressure" => "Press".to_string(),
+        "volume" => "Vol".to_string(),
+        "pxx" => "Pxx".to_string(),
+        "pyy" => "Pyy".to_string(),
+        "pzz" => "Pzz".to_string(),
+        "pxy" => "Pxy".to_string(),
+        "pxz" => "Pxz".to_string(),
+        "pyz" => "Pyz".to_string(),
+        // Energy of one term of the potential, by name
+        x if x.starts_with("energy_") => format!("En_{}", &x["energy_".len()..]),
         _ => panic!("Unknown field name {}", field_name),
     }
 }
@@ -152,10 +171,14 @@ fn construct_redirect(redirect_definition: &yaml_rust::Yaml) -> Option<LogsRedir
                 name: "xyz".to_string(),
                 sections: HashMap::from([(
                     "xyz".to_string(),
-                    vec!["name", "x", "y", "z"]
-                        .iter()
-                        .map(|x| x.to_string())
-                        .collect::<Vec<String>>(),
+                    // Unwrapped coordinates keep trajectories continuous across periodic boundaries
+                    match redirect_definition["unwrapped"].as_bool() {
+                        Some(true) => vec!["name", "xu", "yu", "zu"],
+                        _ => vec!["name", "x", "y", "z"],
+                    }
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<String>>(),
                 )]),
                 precision: match redirect_definition["precision"].as_i64() {
                     Some(x) => x as usize,
@@ -183,7 +206,7 @@ fn construct_redirect(redirect_definition: &yaml_rust::Yaml) -> Option<LogsRedir
 }
 
 impl SimulationLogger {
-    pub fn from(yaml: &yaml_rust::Yaml) -> SimulationLogger {
+    pub fn from(yaml: &yaml_rust::Yaml, potential_model: &PotentialModel) -> SimulationLogger {
         let frequency = match yaml["frequency"] {
             yaml_rust::Yaml::BadValue => 1,
             yaml_rust::Yaml::Integer(frequency) => match frequency > 0 {
@@ -227,6 +250,18 @@ impl SimulationLogger {
             }
         }
 
+        // Energy terms are checked against the potential up front rather than at the first log
+        for field in valid_redirects
+            .iter()
+            .flat_map(|redirect| redirect.sections.values().flatten())
+        {
+            if let Some(name) = field.strip_prefix("energy_") {
+                if !potential_model.has_term(name) {
+                    panic!("Unknown potential term {}", name);
+                }
+            }
+        }
+
         SimulationLogger {
             frequency,
             redirects: valid_redirects,
@@ -377,6 +412,19 @@ impl SimulationLogger {
                                     "x" => Some(self.format_value(atom.current.position[0])),
                                     "y" => Some(self.format_value(atom.current.position[1])),
                                     "z" => Some(self.format_value(atom.current.position[2])),
+                                    "xu" | "yu" | "zu" => {
+                                        let unwrapped_position =
+                                            simulation.system.unwrapped_position(atom);
+                                        let component = match field.as_str() {
+                                            "xu" => 0,
+                                            "yu" => 1,
+                                            _ => 2,
+                                        };
+                                        Some(self.format_value(unwrapped_position[component]))
+                                    }
+                                    "ix" => Some(format!("{:}", atom.image[0])),
+                                    "iy" => Some(format!("{:}", atom.image[1])),
+                                    "iz" => Some(format!("{:}", atom.image[2])),
                                     "type" => Some(format!("{:}", atom.name)),
                                     "vx" => Some(self.format_value(atom.current.velocity[0])),
                                     "vy" => Some(self.format_value(atom.current.velocity[1])),
@@ -386,6 +434,15 @@ impl SimulationLogger {
                                     "fz" => Some(self.format_value(atom.current.force[2])),
                                     "mass" => Some(self.format_value(atom.mass)),
                                     "charge" => Some(self.format_value(atom.charge)),
+                                    "potential_energy" => {
+                                        Some(self.format_value(atom.current.potential_energy))
+                                    }
+                                    "kinetic_energy" => {
+                                        Some(self.format_value(atom.current.kinetic_energy))
+                                    }
+                                    "total_energy" => {
+                                        Some(self.format_value(atom.current.total_energy))
+                                    }
                                     _ => None,
                                 };
                                 match field_value {
@@ -414,9 +471,46 @@ impl SimulationLogger {
                                 "total_energy" => {
                                     Some(self.format_value(simulation.energetics.total_energy))
                                 }
+                                "conserved_energy" => {
+                                    Some(self.format_value(simulation.energetics.conserved_energy))
+                                }
                                 "temperature" => {
                                     Some(self.format_value(simulation.energetics.temperature))
                                 }
+                                "pressure" => {
+                                    Some(self.format_value(simulation.energetics.pressure))
+                                }
+                                "max_force" => {
+                                    Some(self.format_value(simulation.energetics.max_force))
+                                }
+                                "volume" => {
+                                    Some(self.format_value(simulation.system.simulation_box.volume()))
+                                }
+                                "pxx" => Some(self.format_value(
+                                    simulation.energetics.pressure_tensor[(0, 0)],
+                                )),
+                                "pyy" => Some(self.format_value(
+                                    simulation.energetics.pressure_tensor[(1, 1)],
+                                )),
+                                "pzz" => Some(self.format_value(
+                                    simulation.energetics.pressure_tensor[(2, 2)],
+                                )),
+                                "pxy" => Some(self.format_value(
+                                    simulation.energetics.pressure_tensor[(0, 1)],
+                                )),
+                                "pxz" => Some(self.format_value(
+                                    simulation.energetics.pressure_tensor[(0, 2)],
+                                )),
+                                "pyz" => Some(self.format_value(
+                                    simulation.energetics.pressure_tensor[(1, 2)],
+                                )),
+                                x if x.starts_with("energy_") => {
+                                    let name = &x["energy_".len()..];
+                                    match simulation.potential_model.term_energy(name) {
+                                        Some(energy) => Some(self.format_value(energy)),
+                                        None => panic!("Unknown potential term {}", name),
+                                    }
+                                }
                                 _ => None,
                             };
                             match found_value {
@@ -1,5 +1,9 @@
 use yaml_rust::Yaml;
 
+pub const BOLTZMANN_CONSTANT: f64 = 1.380649e-23; // J/K
+pub const VACUUM_PERMITTIVITY: f64 = 8.8541878128e-12; // F/m
+pub const ELEMENTARY_CHARGE: f64 = 1.602176634e-19; // C
+
 #[derive(Debug)]
 pub struct UnitSystem {
     pub name: String,
@@ -30,7 +34,7 @@ impl UnitSystem {
                 temperature: (1.0, String::from("K")),
                 energy: (1.602176634e-19, String::from("eV")),
                 force: (1.0, String::from("N")),
-                pressure: (1.0, String::from("Pa")),
+                pressure: (1e5, String::from("bar")),
             },
             "SI" => UnitSystem {
                 name: String::from("Standard International"),
@@ -46,19 +50,39 @@ impl UnitSystem {
             _ => panic!("Unknown unit system"),
         };
 
-        // Just calculate the rest of the units from the base units
-        units.force.0 = units.mass.0 * units.distance.0 / units.time.0.powi(2);
-        units.pressure.0 = units.force.0 / units.distance.0.powi(2);
+        // Forces are evaluated as energy per distance, so derive their unit from the base units
+        units.force.0 = units.energy.0 / units.distance.0;
         units
     }
+
+    // Boltzmann constant expressed in energy units per temperature unit
+    pub fn boltzmann_constant(&self) -> f64 {
+        BOLTZMANN_CONSTANT * self.temperature.0 / self.energy.0
+    }
+
+    // Coulomb constant 1 / (4 pi epsilon_0) expressed in energy * distance / charge^2 units
+    pub fn coulomb_constant(&self) -> f64 {
+        self.charge.0.powi(2)
+            / (4.0 * std::f64::consts::PI * VACUUM_PERMITTIVITY * self.energy.0 * self.distance.0)
+    }
+
+    // Factor converting mass * velocity^2 (in system units) into energy units
+    pub fn kinetic_energy_factor(&self) -> f64 {
+        self.mass.0 * (self.distance.0 / self.time.0).powi(2) / self.energy.0
+    }
+
+    // Factor converting energy per volume (in system units) into pressure units
+    pub fn pressure_factor(&self) -> f64 {
+        self.energy.0 / self.distance.0.powi(3) / self.pressure.0
+    }
 }
 
 impl std::fmt::Display for UnitSystem {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let indent = 4;
         let unit_system_description = format!(
-            "{:indent$}Name: {}\n{:indent$}Distance: {}\n{:indent$}Time: {}\n{:indent$}Mass: {}\n{:indent$}Charge: {}\n{:indent$}Temperature: {}\n{:indent$}Energy: {}",
-            "", self.name, "", self.distance.1, "", self.time.1, "", self.mass.1, "", self.charge.1, "", self.temperature.1, "", self.energy.1, indent=indent
+            "{:indent$}Name: {}\n{:indent$}Distance: {}\n{:indent$}Time: {}\n{:indent$}Mass: {}\n{:indent$}Charge: {}\n{:indent$}Temperature: {}\n{:indent$}Energy: {}\n{:indent$}Pressure: {}",
+            "", self.name, "", self.distance.1, "", self.time.1, "", self.mass.1, "", self.charge.1, "", self.temperature.1, "", self.energy.1, "", self.pressure.1, indent=indent
         );
         write!(f, "Unit system:\n{}", unit_system_description)
     }
@@ -1,2 +1,3 @@
 pub mod logger;
 pub mod metrics;
+pub mod spline;
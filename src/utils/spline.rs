@@ -0,0 +1,182 @@
+// Cubic spline through tabulated values, natural at both ends. Outside of the grid the
+// function is continued linearly with the slope found at the closest end.
+#[derive(Clone)]
+pub struct Spline {
+    knots: Vec<f64>,
+    values: Vec<f64>,
+    second_derivatives: Vec<f64>,
+    step: Option<f64>, // Spacing of uniform grids, whose intervals are found without searching
+}
+
+impl Spline {
+    pub fn uniform(start: f64, step: f64, values: Vec<f64>) -> Spline {
+        if step <= 0.0 {
+            panic!("Spline grid spacing must be positive");
+        }
+        let knots = (0..values.len()).map(|i| start + step * i as f64).collect();
+        Spline {
+            step: Some(step),
+            ..Spline::cubic(knots, values)
+        }
+    }
+    pub fn cubic(knots: Vec<f64>, values: Vec<f64>) -> Spline {
+        let mut spline = Spline::linear(knots, values);
+        let (x, y) = (&spline.knots, &spline.values);
+        let points_count = x.len();
+        // h[i-1] M[i-1] + 2 (h[i-1] + h[i]) M[i] + h[i] M[i+1]
+        //   = 6 ((y[i+1] - y[i]) / h[i] - (y[i] - y[i-1]) / h[i-1]), with M = 0 at both ends,
+        // solved with the Thomas algorithm
+        let mut diagonal = vec![0.0; points_count];
+        let mut right_hand_side = vec![0.0; points_count];
+        for i in 1..points_count - 1 {
+            let (previous, next) = (x[i] - x[i - 1], x[i + 1] - x[i]);
+            diagonal[i] = 2.0 * (previous + next);
+            right_hand_side[i] = 6.0 * ((y[i + 1] - y[i]) / next - (y[i] - y[i - 1]) / previous);
+            if i > 1 {
+                let factor = previous / diagonal[i - 1];
+                diagonal[i] -= factor * previous;
+                right_hand_side[i] -= factor * right_hand_side[i - 1];
+            }
+        }
+        for i in (1..points_count - 1).rev() {
+            spline.second_derivatives[i] = (right_hand_side[i]
+                - (x[i + 1] - x[i]) * spline.second_derivatives[i + 1])
+                / diagonal[i];
+        }
+        spline
+    }
+    // Piecewise linear interpolation, which is a spline without curvature
+    pub fn linear(knots: Vec<f64>, values: Vec<f64>) -> Spline {
+        if knots.len() < 2 || knots.len() != values.len() {
+            panic!("A spline needs at least two points and one value per point");
+        }
+        if knots.windows(2).any(|x| x[1] <= x[0]) {
+            panic!("Spline points must be strictly increasing");
+        }
+        Spline {
+            second_derivatives: vec![0.0; knots.len()],
+            knots,
+            values,
+            step: None,
+        }
+    }
+    pub fn start(&self) -> f64 {
+        self.knots[0]
+    }
+    pub fn end(&self) -> f64 {
+        self.knots[self.knots.len() - 1]
+    }
+    fn interval(&self, x: f64) -> usize {
+        let k = match self.step {
+            Some(step) => ((x - self.start()) / step).floor() as usize,
+            None => self
+                .knots
+                .partition_point(|knot| *knot <= x)
+                .saturating_sub(1),
+        };
+        k.min(self.knots.len() - 2)
+    }
+    // Value and first derivative at x
+    pub fn evaluate(&self, x: f64) -> (f64, f64) {
+        if x < self.start() || x > self.end() {
+            let edge = x.clamp(self.start(), self.end());
+            let (value, derivative) = self.evaluate(edge);
+            return (value + derivative * (x - edge), derivative);
+        }
+        let k = self.interval(x);
+        let h = self.knots[k + 1] - self.knots[k];
+        let u = (x - self.knots[k]) / h;
+        let v = 1.0 - u;
+        let (y0, y1) = (self.values[k], self.values[k + 1]);
+        let (m0, m1) = (self.second_derivatives[k], self.second_derivatives[k + 1]);
+        let value =
+            v * y0 + u * y1 + ((v.powi(3) - v) * m0 + (u.powi(3) - u) * m1) * h.powi(2) / 6.0;
+        let derivative =
+            (y1 - y0) / h + ((3.0 * u.powi(2) - 1.0) * m1 - (3.0 * v.powi(2) - 1.0) * m0) * h / 6.0;
+        (value, derivative)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Piecewise cubic with a knot at 1, continuous up to its second derivative, which vanishes
+    // at both ends of [0, 2]. Natural splines whose knots include 1 reproduce it exactly.
+    fn natural_cubic(x: f64) -> (f64, f64) {
+        match x < 1.0 {
+            true => (x.powi(3), 3.0 * x.powi(2)),
+            false => {
+                let t = x - 1.0;
+                (
+                    1.0 + 3.0 * t + 3.0 * t.powi(2) - t.powi(3),
+                    3.0 + 6.0 * t - 3.0 * t.powi(2),
+                )
+            }
+        }
+    }
+
+    fn assert_close(found: f64, expected: f64) {
+        assert!(
+            (found - expected).abs() < 1e-12,
+            "{} instead of {}",
+            found,
+            expected
+        );
+    }
+
+    #[test]
+    fn cubic_is_reproduced_exactly() {
+        let uniform = Spline::uniform(
+            0.0,
+            0.25,
+            (0..9).map(|i| natural_cubic(0.25 * i as f64).0).collect(),
+        );
+        let knots = vec![0.0, 0.3, 0.55, 1.0, 1.2, 1.7, 2.0];
+        let values = knots.iter().map(|x| natural_cubic(*x).0).collect();
+        let nonuniform = Spline::cubic(knots, values);
+        for spline in [uniform, nonuniform] {
+            for i in 0..=40 {
+                let x = 0.05 * i as f64;
+                let (value, derivative) = spline.evaluate(x);
+                let (expected_value, expected_derivative) = natural_cubic(x);
+                assert_close(value, expected_value);
+                assert_close(derivative, expected_derivative);
+            }
+            // Continued linearly past both ends
+            let (value, derivative) = spline.evaluate(2.5);
+            assert_close(derivative, natural_cubic(2.0).1);
+            assert_close(value, natural_cubic(2.0).0 + 0.5 * derivative);
+        }
+    }
+
+    #[test]
+    fn derivatives_are_continuous_across_knots() {
+        let knots = (0..12)
+            .map(|i| 0.4 * i as f64 + 0.05 * (i % 3) as f64)
+            .collect::<Vec<f64>>();
+        let values = knots
+            .iter()
+            .map(|x| (1.3 * x).sin() + 0.1 * x * x)
+            .collect();
+        let spline = Spline::cubic(knots.clone(), values);
+        let step = 1e-6;
+        for knot in &knots[1..knots.len() - 1] {
+            let (left_value, left_derivative) = spline.evaluate(knot - step);
+            let (right_value, right_derivative) = spline.evaluate(knot + step);
+            assert!((right_value - left_value).abs() < 1e-5);
+            assert!((right_derivative - left_derivative).abs() < 1e-5);
+            // Second derivatives match as well, seen from the derivatives on both sides
+            let left_curvature = (left_derivative - spline.evaluate(knot - 2.0 * step).1) / step;
+            let right_curvature = (spline.evaluate(knot + 2.0 * step).1 - right_derivative) / step;
+            assert!((right_curvature - left_curvature).abs() < 1e-3);
+        }
+        // The derivative is the one of the interpolated values
+        for i in 0..50 {
+            let x = 0.09 * i as f64 + 0.01;
+            let numerical_derivative =
+                (spline.evaluate(x + step).0 - spline.evaluate(x - step).0) / (2.0 * step);
+            assert!((spline.evaluate(x).1 - numerical_derivative).abs() < 1e-8);
+        }
+    }
+}